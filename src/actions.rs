@@ -0,0 +1,269 @@
+use bevy::{input::InputSystem, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub struct ActionsPlugin;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Dash,
+    Fire,
+    AimUp,
+    AimDown,
+    AimLeft,
+    AimRight,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Dash,
+        Action::Fire,
+        Action::AimUp,
+        Action::AimDown,
+        Action::AimLeft,
+        Action::AimRight,
+        Action::Pause,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Jump => "Jump",
+            Action::Dash => "Dash",
+            Action::Fire => "Fire",
+            Action::AimUp => "Aim up",
+            Action::AimDown => "Aim down",
+            Action::AimLeft => "Aim left",
+            Action::AimRight => "Aim right",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Pad(GamepadButtonType),
+    /// Active once the axis passes the threshold, which is negative for the negative direction.
+    PadAxis(GamepadAxisType, f32),
+}
+
+impl Binding {
+    /// Keyboard and mouse count as one device, gamepad buttons as another. Stick axes never
+    /// match so that rebinding a button leaves analog movement alone.
+    pub fn same_device(&self, other: &Binding) -> bool {
+        use Binding::*;
+
+        matches!(
+            (self, other),
+            (Key(_) | Mouse(_), Key(_) | Mouse(_)) | (Pad(_), Pad(_))
+        )
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key:?}"),
+            Binding::Mouse(button) => write!(f, "Mouse {button:?}"),
+            Binding::Pad(button) => write!(f, "Pad {button:?}"),
+            Binding::PadAxis(axis, threshold) => match *threshold < 0.0 {
+                true => write!(f, "{axis:?}-"),
+                false => write!(f, "{axis:?}+"),
+            },
+        }
+    }
+}
+
+pub fn default_bindings() -> Vec<(Action, Binding)> {
+    use Action::*;
+    use Binding::*;
+
+    vec![
+        (MoveLeft, Key(KeyCode::A)),
+        (MoveLeft, Key(KeyCode::Left)),
+        (MoveLeft, Pad(GamepadButtonType::DPadLeft)),
+        (MoveLeft, PadAxis(GamepadAxisType::LeftStickX, -0.5)),
+        (MoveRight, Key(KeyCode::D)),
+        (MoveRight, Key(KeyCode::Right)),
+        (MoveRight, Pad(GamepadButtonType::DPadRight)),
+        (MoveRight, PadAxis(GamepadAxisType::LeftStickX, 0.5)),
+        (Jump, Key(KeyCode::W)),
+        (Jump, Key(KeyCode::Up)),
+        (Jump, Pad(GamepadButtonType::South)),
+        (Dash, Key(KeyCode::LShift)),
+        (Dash, Mouse(MouseButton::Right)),
+        (Dash, Pad(GamepadButtonType::East)),
+        (Dash, Pad(GamepadButtonType::LeftTrigger2)),
+        (Fire, Key(KeyCode::Space)),
+        (Fire, Mouse(MouseButton::Left)),
+        (Fire, Pad(GamepadButtonType::West)),
+        (Fire, Pad(GamepadButtonType::RightTrigger2)),
+        (AimUp, Key(KeyCode::E)),
+        (AimUp, Pad(GamepadButtonType::DPadUp)),
+        (AimUp, PadAxis(GamepadAxisType::RightStickY, 0.3)),
+        (AimDown, Key(KeyCode::S)),
+        (AimDown, Key(KeyCode::Down)),
+        (AimDown, Pad(GamepadButtonType::DPadDown)),
+        (AimDown, PadAxis(GamepadAxisType::LeftStickY, -0.5)),
+        (AimDown, PadAxis(GamepadAxisType::RightStickY, -0.3)),
+        (AimLeft, PadAxis(GamepadAxisType::RightStickX, -0.3)),
+        (AimRight, PadAxis(GamepadAxisType::RightStickX, 0.3)),
+        (Pause, Key(KeyCode::P)),
+        (Pause, Pad(GamepadButtonType::Start)),
+    ]
+}
+
+/// Which raw inputs drive each action for one entity.
+#[derive(Component, Clone)]
+pub struct InputMap {
+    pub bindings: Vec<(Action, Binding)>,
+    /// Whether `Key` and `Mouse` bindings apply, so players sharing a machine can be split between
+    /// the keyboard and gamepads.
+    pub keyboard_mouse: bool,
+    /// The gamepad that `Pad` and `PadAxis` bindings read from.
+    pub gamepad: Option<Gamepad>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            bindings: default_bindings(),
+            keyboard_mouse: true,
+            gamepad: Some(Gamepad::new(0)),
+        }
+    }
+}
+
+/// The actions resolved from an entity's `InputMap` this frame. Gameplay should read this rather
+/// than raw input.
+#[derive(Component, Default)]
+pub struct ActionState {
+    values: HashMap<Action, f32>,
+    previous: HashMap<Action, f32>,
+}
+
+impl ActionState {
+    /// How strongly the action is held, from `0.0` to `1.0`. Digital inputs are always `1.0`.
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or_default()
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action) > 0.0
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.pressed(action) && !self.previous.get(&action).is_some_and(|value| *value > 0.0)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        !self.pressed(action) && self.previous.get(&action).is_some_and(|value| *value > 0.0)
+    }
+
+    /// Starts a new frame with `values`, keeping the last frame's to detect edges against.
+    fn update(&mut self, values: HashMap<Action, f32>) {
+        self.previous = std::mem::replace(&mut self.values, values);
+    }
+}
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PreUpdate, update_actions.after(InputSystem));
+    }
+}
+
+fn update_actions(
+    mut query: Query<(&InputMap, &mut ActionState)>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+) {
+    for (map, mut state) in query.iter_mut() {
+        let mut values: HashMap<Action, f32> = HashMap::default();
+
+        for (action, binding) in map.bindings.iter() {
+            let value = match *binding {
+                Binding::Key(_) | Binding::Mouse(_) if !map.keyboard_mouse => 0.0,
+                Binding::Key(key) => keyboard.pressed(key) as u8 as f32,
+                Binding::Mouse(button) => mouse.pressed(button) as u8 as f32,
+                Binding::Pad(button) => map.gamepad.map_or(0.0, |gamepad| {
+                    gamepad_buttons.pressed(GamepadButton::new(gamepad, button)) as u8 as f32
+                }),
+                Binding::PadAxis(axis, threshold) => map
+                    .gamepad
+                    .and_then(|gamepad| gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                    .map_or(0.0, |value| {
+                        // Flip so that travel in the bound direction is positive
+                        let value = value * threshold.signum();
+                        if value >= threshold.abs() {
+                            value.min(1.0)
+                        } else {
+                            0.0
+                        }
+                    }),
+            };
+
+            let entry = values.entry(*action).or_default();
+            *entry = entry.max(value);
+        }
+
+        state.update(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holding(actions: &[(Action, f32)]) -> HashMap<Action, f32> {
+        actions.iter().copied().collect()
+    }
+
+    #[test]
+    fn press_is_only_fresh_for_one_frame() {
+        let mut state = ActionState::default();
+
+        state.update(holding(&[(Action::Jump, 1.0)]));
+        assert!(state.pressed(Action::Jump));
+        assert!(state.just_pressed(Action::Jump));
+
+        state.update(holding(&[(Action::Jump, 1.0)]));
+        assert!(state.pressed(Action::Jump));
+        assert!(!state.just_pressed(Action::Jump));
+    }
+
+    #[test]
+    fn release_is_only_fresh_for_one_frame() {
+        let mut state = ActionState::default();
+        state.update(holding(&[(Action::Jump, 1.0)]));
+
+        state.update(holding(&[(Action::Jump, 0.0)]));
+        assert!(!state.pressed(Action::Jump));
+        assert!(state.just_released(Action::Jump));
+
+        state.update(HashMap::default());
+        assert!(!state.just_released(Action::Jump));
+    }
+
+    #[test]
+    fn analog_values_count_as_pressed() {
+        let mut state = ActionState::default();
+        state.update(holding(&[(Action::MoveLeft, 0.4)]));
+
+        assert_eq!(state.value(Action::MoveLeft), 0.4);
+        assert!(state.just_pressed(Action::MoveLeft));
+        assert_eq!(state.value(Action::MoveRight), 0.0);
+        assert!(!state.pressed(Action::MoveRight));
+    }
+}
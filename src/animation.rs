@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::GameState;
+
+pub struct AnimationPlugin;
+
+/// Systems that change clips or react to frames should run relative to this.
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnimationSystem;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationMode {
+    Loop,
+    /// Holds the last frame once it has played.
+    Once,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationFrame {
+    pub index: usize,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: String,
+    pub frames: Vec<AnimationFrame>,
+    pub mode: AnimationMode,
+    /// Named events fired when the clip reaches the frame at the given position in `frames`.
+    pub events: Vec<(usize, String)>,
+}
+
+impl AnimationClip {
+    /// Clip showing each atlas index in turn for `1 / fps` seconds.
+    pub fn new(
+        name: impl Into<String>,
+        indices: impl IntoIterator<Item = usize>,
+        fps: f32,
+        mode: AnimationMode,
+    ) -> Self {
+        let duration = Duration::from_secs_f32(1.0 / fps);
+        Self {
+            name: name.into(),
+            frames: indices
+                .into_iter()
+                .map(|index| AnimationFrame { index, duration })
+                .collect(),
+            mode,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn with_event(mut self, frame: usize, name: impl Into<String>) -> Self {
+        self.events.push((frame, name.into()));
+        self
+    }
+}
+
+#[derive(Component)]
+pub struct Animator {
+    clips: Vec<AnimationClip>,
+    current: usize,
+    frame: usize,
+    elapsed: Duration,
+    finished: bool,
+    /// Set when a frame is entered without the timer advancing into it, i.e. by `play`.
+    frame_entered: bool,
+    pub paused: bool,
+}
+
+impl Animator {
+    /// Starts playing the first clip.
+    pub fn new(clips: Vec<AnimationClip>) -> Self {
+        assert!(!clips.is_empty(), "An Animator needs at least one clip");
+        Self {
+            clips,
+            current: 0,
+            frame: 0,
+            elapsed: Duration::ZERO,
+            finished: false,
+            frame_entered: true,
+            paused: false,
+        }
+    }
+
+    /// Switches to the named clip. Does nothing if it is already playing, so this can be called
+    /// every frame.
+    pub fn play(&mut self, name: &str) {
+        if self.clip().name != name {
+            self.restart(name);
+        }
+    }
+
+    /// Plays the named clip from its first frame, even if it is already playing.
+    pub fn restart(&mut self, name: &str) {
+        self.current = self
+            .clips
+            .iter()
+            .position(|clip| clip.name == name)
+            .unwrap_or_else(|| panic!("No animation clip named {name}"));
+        self.frame = 0;
+        self.elapsed = Duration::ZERO;
+        self.finished = false;
+        self.frame_entered = true;
+    }
+
+    pub fn clip(&self) -> &AnimationClip {
+        &self.clips[self.current]
+    }
+
+    /// Position of the current frame within the playing clip.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Whether a `Once` clip has played its last frame.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Advances by `delta`, returning the positions of every frame entered along the way.
+    fn advance(&mut self, delta: Duration) -> Vec<usize> {
+        let mut entered = Vec::new();
+        if std::mem::take(&mut self.frame_entered) {
+            entered.push(self.frame);
+        }
+
+        if self.finished {
+            return entered;
+        }
+
+        self.elapsed += delta;
+        let clip = &self.clips[self.current];
+
+        // Bounded by the clip length so zero-length frames cannot spin forever
+        for _ in 0..clip.frames.len() {
+            let duration = clip.frames[self.frame].duration;
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+
+            if self.frame + 1 < clip.frames.len() {
+                self.frame += 1;
+            } else if clip.mode == AnimationMode::Loop {
+                self.frame = 0;
+            } else {
+                self.finished = true;
+                self.elapsed = Duration::ZERO;
+                break;
+            }
+            entered.push(self.frame);
+        }
+
+        entered
+    }
+}
+
+/// Fired when an `Animator` enters a frame that has an event attached in its clip.
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AnimationEvent>()
+            .add_system(animate.label(AnimationSystem));
+    }
+}
+
+fn animate(
+    mut query: Query<(Entity, &mut Animator, &mut TextureAtlasSprite)>,
+    mut events: EventWriter<AnimationEvent>,
+    state: Res<State<GameState>>,
+    time: Res<Time>,
+) {
+    if state.current().freezes_gameplay() {
+        return;
+    }
+
+    for (id, mut animator, mut sprite) in query.iter_mut() {
+        if animator.paused {
+            continue;
+        }
+
+        for frame in animator.advance(time.delta()) {
+            for (_, name) in animator
+                .clip()
+                .events
+                .iter()
+                .filter(|(event_frame, _)| *event_frame == frame)
+            {
+                events.send(AnimationEvent {
+                    entity: id,
+                    name: name.clone(),
+                });
+            }
+        }
+
+        let index = animator.clip().frames[animator.frame()].index;
+        if sprite.index != index {
+            sprite.index = index;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    fn animator(mode: AnimationMode) -> Animator {
+        let frames = (0..3)
+            .map(|index| AnimationFrame {
+                index,
+                duration: FRAME,
+            })
+            .collect();
+        Animator::new(vec![AnimationClip {
+            name: "clip".to_string(),
+            frames,
+            mode,
+            events: Vec::new(),
+        }])
+    }
+
+    #[test]
+    fn loop_wraps_to_the_first_frame() {
+        let mut animator = animator(AnimationMode::Loop);
+
+        assert_eq!(animator.advance(Duration::ZERO), vec![0]);
+        assert_eq!(animator.advance(FRAME * 2), vec![1, 2]);
+        assert_eq!(animator.advance(FRAME), vec![0]);
+        assert!(!animator.finished());
+    }
+
+    #[test]
+    fn once_holds_the_last_frame() {
+        let mut animator = animator(AnimationMode::Once);
+
+        assert_eq!(animator.advance(FRAME * 2), vec![0, 1, 2]);
+        assert!(!animator.finished());
+        assert!(animator.advance(FRAME).is_empty());
+        assert!(animator.finished());
+        assert_eq!(animator.frame(), 2);
+        assert!(animator.advance(FRAME * 10).is_empty());
+    }
+
+    #[test]
+    fn each_frame_of_a_once_clip_is_entered_exactly_once() {
+        let mut animator = animator(AnimationMode::Once);
+
+        let entered: Vec<usize> = (0..40)
+            .flat_map(|_| animator.advance(Duration::from_millis(16)))
+            .collect();
+        assert_eq!(entered, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn restart_enters_the_first_frame_again() {
+        let mut animator = animator(AnimationMode::Once);
+        animator.advance(FRAME * 5);
+        assert!(animator.finished());
+
+        animator.restart("clip");
+        assert!(!animator.finished());
+        assert_eq!(animator.advance(Duration::ZERO), vec![0]);
+    }
+
+    #[test]
+    fn play_does_not_restart_the_current_clip() {
+        let mut animator = animator(AnimationMode::Loop);
+        animator.advance(FRAME);
+
+        animator.play("clip");
+        assert_eq!(animator.frame(), 1);
+        assert!(animator.advance(Duration::ZERO).is_empty());
+    }
+}
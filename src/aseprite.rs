@@ -0,0 +1,343 @@
+use anyhow::anyhow;
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    sprite::Rect,
+    utils::BoxedFuture,
+};
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::animation::{AnimationClip, AnimationFrame, AnimationMode, Animator};
+
+pub struct AsepritePlugin;
+
+/// A sprite sheet exported from Aseprite with `File > Export Sprite Sheet`, using the "Array"
+/// JSON data format with tags and slices enabled and trimming disabled.
+///
+/// Load the `.aseprite.json` file; the atlas is also available as the `atlas` label, e.g.
+/// `LittleGuy.aseprite.json#atlas`.
+#[derive(TypeUuid)]
+#[uuid = "6f0b4a3e-2c1d-4b7a-9c55-3e8f1d2a7b90"]
+pub struct AsepriteSheet {
+    pub atlas: Handle<TextureAtlas>,
+    /// One clip per frame tag, named after the tag.
+    pub clips: Vec<AnimationClip>,
+    pub slices: Vec<AsepriteSlice>,
+    pub frame_size: Vec2,
+}
+
+pub struct AsepriteSlice {
+    pub name: String,
+    /// Bounds in sheet pixels from the top left of the frame, keyed by the first frame they
+    /// apply to.
+    pub keys: Vec<(usize, Rect)>,
+}
+
+impl AsepriteSheet {
+    /// Bounds of the named slice on `frame`, relative to the centre of the sprite with y up.
+    pub fn slice(&self, name: &str, frame: usize) -> Option<Rect> {
+        let slice = self.slices.iter().find(|slice| slice.name == name)?;
+        let (_, bounds) = slice
+            .keys
+            .iter()
+            .rev()
+            .find(|(key_frame, _)| *key_frame <= frame)?;
+
+        let half = self.frame_size / 2.0;
+        Some(Rect {
+            min: Vec2::new(bounds.min.x - half.x, half.y - bounds.max.y),
+            max: Vec2::new(bounds.max.x - half.x, half.y - bounds.min.y),
+        })
+    }
+}
+
+/// Gives the entity an `Animator` with the sheet's clips once it has loaded. If the sheet has a
+/// `hitbox` slice, the entity's `Collider` is resized to match it.
+#[derive(Component)]
+pub struct AsepriteAnimation(pub Handle<AsepriteSheet>);
+
+#[derive(Deserialize)]
+struct JsonRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl From<&JsonRect> for Rect {
+    fn from(rect: &JsonRect) -> Self {
+        Rect {
+            min: Vec2::new(rect.x, rect.y),
+            max: Vec2::new(rect.x + rect.w, rect.y + rect.h),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrame {
+    frame: JsonRect,
+    source_size: JsonSize,
+    duration: u64,
+}
+
+#[derive(Deserialize)]
+struct JsonTag {
+    name: String,
+    from: usize,
+    to: usize,
+    direction: String,
+    /// Only written by Aseprite when the tag repeats a fixed number of times.
+    repeat: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JsonSliceKey {
+    frame: usize,
+    bounds: JsonRect,
+}
+
+#[derive(Deserialize)]
+struct JsonSlice {
+    name: String,
+    keys: Vec<JsonSliceKey>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonMeta {
+    image: String,
+    size: JsonSize,
+    #[serde(default)]
+    frame_tags: Vec<JsonTag>,
+    #[serde(default)]
+    slices: Vec<JsonSlice>,
+}
+
+#[derive(Deserialize)]
+struct JsonSheet {
+    frames: Vec<JsonFrame>,
+    meta: JsonMeta,
+}
+
+impl JsonSheet {
+    /// One clip per frame tag.
+    fn clips(&self) -> Result<Vec<AnimationClip>, anyhow::Error> {
+        self.meta
+            .frame_tags
+            .iter()
+            .map(|tag| {
+                let mut indices: Vec<usize> = (tag.from..=tag.to).collect();
+                match tag.direction.as_str() {
+                    "reverse" => indices.reverse(),
+                    "pingpong" => {
+                        let back: Vec<usize> = indices
+                            .iter()
+                            .rev()
+                            .skip(1)
+                            .take(indices.len().saturating_sub(2))
+                            .copied()
+                            .collect();
+                        indices.extend(back);
+                    }
+                    _ => {}
+                }
+
+                // Tags are hand-editable, so don't trust them to stay within the sheet
+                let frames = indices
+                    .into_iter()
+                    .map(|index| match self.frames.get(index) {
+                        Some(frame) => Ok(AnimationFrame {
+                            index,
+                            duration: Duration::from_millis(frame.duration),
+                        }),
+                        None => Err(anyhow!(
+                            "tag `{}` refers to frame {index}, but the sheet has {}",
+                            tag.name,
+                            self.frames.len()
+                        )),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(AnimationClip {
+                    name: tag.name.clone(),
+                    frames,
+                    mode: match tag.repeat.as_deref() {
+                        Some("1") => AnimationMode::Once,
+                        _ => AnimationMode::Loop,
+                    },
+                    events: Vec::new(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct AsepriteLoader;
+
+impl AssetLoader for AsepriteLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let json: JsonSheet = serde_json::from_slice(bytes)?;
+
+            let image_path = load_context
+                .path()
+                .parent()
+                .map(|dir| dir.join(&json.meta.image))
+                .unwrap_or_else(|| json.meta.image.clone().into());
+            let image_path = AssetPath::new(image_path, None);
+            let image = load_context.get_handle(image_path.clone());
+
+            let mut atlas =
+                TextureAtlas::new_empty(image, Vec2::new(json.meta.size.w, json.meta.size.h));
+            for frame in json.frames.iter() {
+                atlas.add_texture((&frame.frame).into());
+            }
+            let atlas = load_context
+                .set_labeled_asset("atlas", LoadedAsset::new(atlas).with_dependency(image_path));
+
+            let clips = json.clips()?;
+
+            let slices = json
+                .meta
+                .slices
+                .iter()
+                .map(|slice| AsepriteSlice {
+                    name: slice.name.clone(),
+                    keys: slice
+                        .keys
+                        .iter()
+                        .map(|key| (key.frame, (&key.bounds).into()))
+                        .collect(),
+                })
+                .collect();
+
+            let frame_size = json
+                .frames
+                .first()
+                .map(|frame| Vec2::new(frame.source_size.w, frame.source_size.h))
+                .unwrap_or_default();
+
+            load_context.set_default_asset(LoadedAsset::new(AsepriteSheet {
+                atlas,
+                clips,
+                slices,
+                frame_size,
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["aseprite.json"]
+    }
+}
+
+impl Plugin for AsepritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AsepriteSheet>()
+            .init_asset_loader::<AsepriteLoader>()
+            .add_system(attach_aseprite_animations);
+    }
+}
+
+fn attach_aseprite_animations(
+    mut commands: Commands,
+    query: Query<(Entity, &AsepriteAnimation, Option<&Collider>), Without<Animator>>,
+    sheets: Res<Assets<AsepriteSheet>>,
+) {
+    for (id, animation, collider) in query.iter() {
+        if let Some(sheet) = sheets.get(&animation.0) {
+            commands
+                .entity(id)
+                .insert(Animator::new(sheet.clips.clone()))
+                .insert(sheet.atlas.clone());
+
+            if let (Some(_), Some(hitbox)) = (collider, sheet.slice("hitbox", 0)) {
+                let half_extents = (hitbox.max - hitbox.min) / 2.0;
+                commands
+                    .entity(id)
+                    .insert(Collider::cuboid(half_extents.x, half_extents.y));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(frames: usize, tags: &str) -> JsonSheet {
+        let frame = r#"{
+            "frame": { "x": 0, "y": 0, "w": 16, "h": 16 },
+            "sourceSize": { "w": 16, "h": 16 },
+            "duration": 100
+        }"#;
+        let frames = vec![frame; frames].join(",");
+        serde_json::from_str(&format!(
+            r#"{{
+                "frames": [{frames}],
+                "meta": {{
+                    "image": "Sheet.png",
+                    "size": {{ "w": 64, "h": 16 }},
+                    "frameTags": [{tags}]
+                }}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn tags_become_clips() {
+        let clips = sheet(
+            4,
+            r#"{ "name": "run", "from": 1, "to": 3, "direction": "pingpong" },
+               { "name": "hit", "from": 0, "to": 0, "direction": "forward", "repeat": "1" }"#,
+        )
+        .clips()
+        .unwrap();
+
+        assert_eq!(clips.len(), 2);
+        let indices: Vec<usize> = clips[0].frames.iter().map(|frame| frame.index).collect();
+        assert_eq!(indices, vec![1, 2, 3, 2]);
+        assert_eq!(clips[0].mode, AnimationMode::Loop);
+        assert_eq!(clips[0].frames[0].duration, Duration::from_millis(100));
+        assert_eq!(clips[1].mode, AnimationMode::Once);
+    }
+
+    #[test]
+    fn tag_past_the_last_frame_is_an_error() {
+        let result = sheet(
+            2,
+            r#"{ "name": "run", "from": 0, "to": 2, "direction": "forward" }"#,
+        )
+        .clips();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_sheet_has_no_clips() {
+        assert!(sheet(0, "").clips().unwrap().is_empty());
+        assert!(sheet(
+            0,
+            r#"{ "name": "idle", "from": 0, "to": 0, "direction": "forward" }"#
+        )
+        .clips()
+        .is_err());
+    }
+}
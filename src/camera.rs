@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::GameState;
+
+pub struct CameraPlugin;
+
+const MAX_SHAKE_OFFSET: f32 = 6.0;
+const MAX_SHAKE_ANGLE: f32 = 0.05;
+const TRAUMA_DECAY_PER_SEC: f32 = 1.5;
+
+/// Player-facing switches for effects that can be uncomfortable to look at, toggled from the
+/// pause screen.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Accessibility {
+    pub screen_shake: bool,
+    pub hit_stop: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self {
+            screen_shake: true,
+            hit_stop: true,
+        }
+    }
+}
+
+#[derive(Component, Inspectable, Default)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+/// Adds trauma to every `CameraShake`. Shake strength is the square of the trauma, so small
+/// amounts barely register while stacked hits build up quickly.
+pub struct Shake(pub f32);
+
+/// Freezes gameplay for the given duration.
+pub struct HitStop(pub Duration);
+
+struct HitStopTimer(Timer);
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Accessibility>()
+            .add_event::<Shake>()
+            .add_event::<HitStop>()
+            .add_system(add_trauma.before(shake_camera))
+            .add_system(shake_camera)
+            .add_system(start_hit_stop)
+            .add_system_set(SystemSet::on_update(GameState::HitStop).with_system(end_hit_stop));
+    }
+}
+
+fn add_trauma(mut events: EventReader<Shake>, mut query: Query<&mut CameraShake>) {
+    for Shake(amount) in events.iter() {
+        for mut shake in query.iter_mut() {
+            shake.trauma = (shake.trauma + amount).min(1.0);
+        }
+    }
+}
+
+fn shake_camera(
+    mut query: Query<(&mut Transform, &mut CameraShake)>,
+    accessibility: Res<Accessibility>,
+    time: Res<Time>,
+) {
+    let mut rng = thread_rng();
+    for (mut transform, mut shake) in query.iter_mut() {
+        shake.trauma = (shake.trauma - TRAUMA_DECAY_PER_SEC * time.delta_seconds()).max(0.0);
+
+        let strength = match accessibility.screen_shake {
+            true => shake.trauma.powi(2),
+            false => 0.0,
+        };
+
+        // The camera rests at the origin, so the offset is the whole translation
+        transform.translation.x = MAX_SHAKE_OFFSET * strength * rng.gen_range(-1.0..=1.0);
+        transform.translation.y = MAX_SHAKE_OFFSET * strength * rng.gen_range(-1.0..=1.0);
+        transform.rotation =
+            Quat::from_rotation_z(MAX_SHAKE_ANGLE * strength * rng.gen_range(-1.0..=1.0));
+    }
+}
+
+fn start_hit_stop(
+    mut commands: Commands,
+    mut events: EventReader<HitStop>,
+    mut state: ResMut<State<GameState>>,
+    accessibility: Res<Accessibility>,
+) {
+    // Only the longest request this frame matters
+    let duration = events.iter().map(|HitStop(duration)| *duration).max();
+
+    if let Some(duration) = duration {
+        if accessibility.hit_stop
+            && *state.current() == GameState::Play
+            && state.push(GameState::HitStop).is_ok()
+        {
+            commands.insert_resource(HitStopTimer(Timer::new(duration, false)));
+        }
+    }
+}
+
+fn end_hit_stop(
+    mut timer: ResMut<HitStopTimer>,
+    mut state: ResMut<State<GameState>>,
+    time: Res<Time>,
+) {
+    timer.0.tick(time.delta());
+    if timer.0.just_finished() {
+        let _ = state.pop();
+    }
+}
@@ -5,7 +5,14 @@ use bevy::{
 use bevy_inspector_egui::{RegisterInspectable, WorldInspectorPlugin};
 use bevy_rapier2d::prelude::RapierDebugRenderPlugin;
 
-use crate::{health::Health, physics::GroundDetection, player::Player};
+use crate::{
+    camera::CameraShake,
+    health::Health,
+    hitbox::Team,
+    physics::{GroundDetection, WallDetection},
+    player::{Aim, Player, Pogo},
+    status::StatusEffects,
+};
 
 pub struct DebugPlugin;
 
@@ -17,8 +24,14 @@ impl Plugin for DebugPlugin {
                 .add_plugin(FrameTimeDiagnosticsPlugin::default())
                 .add_plugin(WorldInspectorPlugin::new())
                 .register_inspectable::<Player>()
+                .register_inspectable::<Aim>()
+                .register_inspectable::<Pogo>()
                 .register_inspectable::<GroundDetection>()
+                .register_inspectable::<WallDetection>()
                 .register_inspectable::<Health>()
+                .register_inspectable::<StatusEffects>()
+                .register_inspectable::<Team>()
+                .register_inspectable::<CameraShake>()
                 .add_system(bevy::window::close_on_esc);
         }
     }
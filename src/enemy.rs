@@ -4,8 +4,12 @@ use rand::{thread_rng, Rng};
 use std::time::Duration;
 
 use crate::{
-    health::{Damaged, Health, Invuln},
-    player::{Laser, Player},
+    animation::{AnimationClip, AnimationEvent, AnimationMode, AnimationSystem, Animator},
+    camera::Shake,
+    health::{DamageKind, DamageResponse, FlashPattern, Health, Hitstun},
+    hitbox::{Hitbox, Hurtbox, Knockback, Team},
+    player::Player,
+    status::{StatusApplication, StatusEffects, StatusKind},
     GameState,
 };
 
@@ -14,6 +18,41 @@ const ENEMY_ONE_COOLDOWN_SECS: f32 = 4.0;
 const ENEMY_ONE_COOLDOWN_VAR: f32 = 1.0;
 const ENEMY_ONE_SPEED: f32 = 70.0;
 const ENEMY_ONE_BEAM_MS: u64 = 600;
+/// A whole heart.
+const ENEMY_ONE_BEAM_DAMAGE: u8 = 2;
+/// Away from the enemy along the beam, and up off the ground.
+const ENEMY_ONE_BEAM_KNOCKBACK: Vec2 = Vec2::new(160.0, 120.0);
+const ENEMY_ONE_BEAM_STATUS: Option<StatusApplication> = Some(StatusApplication {
+    kind: StatusKind::Burn,
+    secs: 1.5,
+});
+/// Highest face of the die that decides how many enemies spawn.
+const MAX_ROLL: u8 = 6;
+/// Rolling the most enemies gives the players a moment while they get their bearings.
+const MAX_ROLL_STUN: StatusApplication = StatusApplication {
+    kind: StatusKind::Stun,
+    secs: 1.5,
+};
+/// How quickly knockback wears off, since enemies float with no friction to stop them.
+const ENEMY_ONE_DAMPING: f32 = 8.0;
+const ENEMY_ONE_BEAM_TRAUMA: f32 = 0.3;
+/// Whether bumping into an enemy one hurts, and by how much.
+const ENEMY_ONE_CONTACT_DAMAGE: Option<Hitbox> = Some(Hitbox {
+    damage: 1,
+    kind: DamageKind::Contact,
+    knockback: Knockback::Away(Vec2::new(140.0, 100.0)),
+    status: None,
+    pierce: None,
+    friendly_fire: false,
+    owner: None,
+});
+const ENEMY_ONE_DAMAGE_RESPONSE: DamageResponse = DamageResponse {
+    invuln: Duration::from_millis(200),
+    hitstun: Duration::from_millis(150),
+    flash: FlashPattern::Tint,
+    flash_period: Duration::from_millis(40),
+    sound: None,
+};
 
 pub struct EnemyPlugin;
 
@@ -26,8 +65,6 @@ struct EnemyCount(u8);
 
 #[derive(Component)]
 pub struct FacePlayer;
-#[derive(Component, Debug)]
-pub struct DiesToLaser;
 
 #[derive(Component)]
 pub struct Dicethulhu;
@@ -56,25 +93,40 @@ impl Default for Beam {
     }
 }
 
+/// `direction` is the sign of x the beam fires towards.
+fn enemy_one_beam_hitbox(enemy: Entity, direction: f32) -> Hitbox {
+    Hitbox {
+        damage: ENEMY_ONE_BEAM_DAMAGE,
+        kind: DamageKind::Beam,
+        knockback: Knockback::Fixed(ENEMY_ONE_BEAM_KNOCKBACK * Vec2::new(direction, 1.0)),
+        status: ENEMY_ONE_BEAM_STATUS,
+        pierce: None,
+        friendly_fire: true,
+        owner: Some(enemy),
+    }
+}
+
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system_to_stage(StartupStage::PreStartup, load_graphics)
             .add_startup_system(spawn_dicethulhu)
-            .add_system(animate_dicethulhu)
             .insert_resource(EnemyCount(5))
             .add_system_set(SystemSet::on_enter(GameState::Play).with_system(spawn_enemy_one))
             .add_system_set(
                 SystemSet::on_update(GameState::Play)
                     .with_system(face_player)
-                    .with_system(check_for_laser)
-                    .with_system(animate_enemy_one)
+                    .with_system(animate_enemy_one.before(AnimationSystem))
+                    .with_system(fire_enemy_one_beam.after(AnimationSystem))
                     .with_system(destroy_beam)
                     .with_system(enemy_one_movement)
                     .with_system(check_enemies_alive),
             )
             .add_system_set(SystemSet::on_enter(GameState::DiceRoll).with_system(spawn_dice))
             .add_system_set(SystemSet::on_exit(GameState::DiceRoll).with_system(despawn_dice))
-            .add_system_set(SystemSet::on_update(GameState::DiceRoll).with_system(animate_dice));
+            .add_system_set(
+                SystemSet::on_update(GameState::DiceRoll)
+                    .with_system(animate_dice.after(AnimationSystem)),
+            );
     }
 }
 
@@ -83,34 +135,48 @@ struct DiceAnim {
     timer: Timer,
 }
 
-fn check_enemies_alive(query: Query<&EnemyOne>, mut state: ResMut<State<GameState>>) {
-    if query.iter().len() == 0 {
-        state.set(GameState::DiceRoll);
+fn check_enemies_alive(
+    mut commands: Commands,
+    query: Query<Entity, With<EnemyOne>>,
+    players: Query<(), With<Player>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    // With every player down the round is lost, and the dice roll revives them
+    if players.is_empty() {
+        for id in query.iter() {
+            commands.entity(id).despawn_recursive();
+        }
+        let _ = state.set(GameState::DiceRoll);
+    } else if query.is_empty() {
+        let _ = state.set(GameState::DiceRoll);
     }
 }
 
 fn animate_dice(
     mut commands: Commands,
     mut state: ResMut<State<GameState>>,
-    mut query: Query<(&mut TextureAtlasSprite, &mut DiceAnim)>,
+    mut query: Query<(
+        Entity,
+        &mut TextureAtlasSprite,
+        &mut DiceAnim,
+        Option<&Animator>,
+    )>,
     time: Res<Time>,
 ) {
-    let (mut sprite, mut timer) = query.single_mut();
+    let (id, mut sprite, mut timer, animator) = query.single_mut();
     timer.timer.tick(time.delta());
 
     if timer.timer.just_finished() {
         state.set(GameState::Play);
     }
 
-    let elapsed = timer.timer.elapsed().as_millis();
-    let frame = (elapsed / 200) + 13;
-    if frame == 26 {
+    // Once the roll has played, land on a face and stop animating
+    if animator.is_some_and(Animator::finished) {
         let mut rng = thread_rng();
-        let face_index = rng.gen_range(0..6);
+        let face_index = rng.gen_range(0..MAX_ROLL);
         commands.insert_resource(EnemyCount(face_index + 1));
-        sprite.index = face_index.try_into().unwrap();
-    } else if frame < 26 {
-        sprite.index = frame.try_into().unwrap();
+        sprite.index = face_index.into();
+        commands.entity(id).remove::<Animator>();
     }
 }
 
@@ -134,6 +200,12 @@ fn spawn_dice(mut commands: Commands, dice_sheet: Res<DiceRollSheet>) {
         .insert(DiceAnim {
             timer: Timer::new(Duration::from_millis(3000), false),
         })
+        .insert(Animator::new(vec![AnimationClip::new(
+            "roll",
+            13..=25,
+            5.0,
+            AnimationMode::Once,
+        )]))
         .insert(Name::from("Dice"));
 }
 
@@ -146,48 +218,37 @@ fn destroy_beam(mut commands: Commands, mut query: Query<(Entity, &mut Beam)>, t
     }
 }
 
-fn check_for_laser(
-    mut commands: Commands,
-    query: Query<(Entity, &DiesToLaser), Without<Invuln>>,
-    laser_query: Query<&Laser>,
-    mut collisions: EventReader<CollisionEvent>,
-) {
-    for collision in collisions.iter() {
-        if let CollisionEvent::Started(a, b, _) = collision {
-            if query.get(*a).is_ok() && laser_query.get(*b).is_ok() {
-                commands.entity(*a).insert(Damaged);
-            } else if query.get(*b).is_ok() && laser_query.get(*a).is_ok() {
-                commands.entity(*b).insert(Damaged);
-            }
-        }
-    }
+/// Position of the living player closest to `position`.
+fn nearest_player<'a>(
+    position: Vec3,
+    players: impl IntoIterator<Item = &'a Transform>,
+) -> Option<Vec3> {
+    players
+        .into_iter()
+        .map(|transform| transform.translation)
+        .min_by(|a, b| {
+            a.distance_squared(position)
+                .total_cmp(&b.distance_squared(position))
+        })
 }
 
 fn face_player(
     mut query: Query<(&FacePlayer, &mut TextureAtlasSprite, &Transform)>,
     player_query: Query<&Transform, With<Player>>,
 ) {
-    let player_transform = player_query.single();
     for (_, mut sprite, transform) in query.iter_mut() {
-        sprite.flip_x =
-            match (player_transform.translation.x - transform.translation.x).signum() as i32 {
-                1 => true,
-                -1 => false,
-                _ => unreachable!(),
-            }
+        let player_translation = match nearest_player(transform.translation, &player_query) {
+            Some(translation) => translation,
+            None => continue,
+        };
+        sprite.flip_x = match (player_translation.x - transform.translation.x).signum() as i32 {
+            1 => true,
+            -1 => false,
+            _ => unreachable!(),
+        }
     }
 }
 
-fn animate_dicethulhu(
-    mut query: Query<&mut TextureAtlasSprite, With<Dicethulhu>>,
-    time: Res<Time>,
-) {
-    let mut dicethulhu_sprite = query.single_mut();
-    dicethulhu_sprite.index = ((time.time_since_startup().as_millis() / 200) % 16)
-        .try_into()
-        .expect("Should always fit into u128");
-}
-
 fn spawn_dicethulhu(mut commands: Commands, sprite_sheet: Res<DicethulhuSheet>) {
     commands
         .spawn_bundle(SpriteSheetBundle {
@@ -199,45 +260,57 @@ fn spawn_dicethulhu(mut commands: Commands, sprite_sheet: Res<DicethulhuSheet>)
             },
             ..Default::default()
         })
+        .insert(Animator::new(vec![AnimationClip::new(
+            "idle",
+            0..=15,
+            5.0,
+            AnimationMode::Loop,
+        )]))
         .insert(Dicethulhu)
         .insert(Name::from("Dicethulhu"));
 }
 
 fn enemy_one_movement(
-    mut query: Query<(&mut Transform, &mut EnemyOne)>,
+    mut query: Query<(&mut Transform, &mut EnemyOne, &StatusEffects), Without<Hitstun>>,
     player_query: Query<&Transform, (With<Player>, Without<EnemyOne>)>,
     time: Res<Time>,
 ) {
-    let player_transform = player_query.single();
-    for (mut enemy_transform, mut enemy_one) in query.iter_mut() {
+    for (mut enemy_transform, mut enemy_one, effects) in query.iter_mut() {
         if enemy_one.state != EnemyOneState::Move {
             continue;
         }
 
-        let y_delta = player_transform.translation.y - enemy_transform.translation.y;
+        let player_translation = match nearest_player(enemy_transform.translation, &player_query) {
+            Some(translation) => translation,
+            None => continue,
+        };
+        let y_delta = player_translation.y - enemy_transform.translation.y;
         if y_delta.abs() < 1.0 {
             enemy_one.state = EnemyOneState::Idle;
         }
         let y_direction = y_delta.signum();
-        enemy_transform.translation.y += ENEMY_ONE_SPEED * y_direction * time.delta_seconds();
+        enemy_transform.translation.y +=
+            ENEMY_ONE_SPEED * effects.speed_multiplier() * y_direction * time.delta_seconds();
     }
 }
 
 fn animate_enemy_one(
-    mut commands: Commands,
-    mut query: Query<(
-        Entity,
-        &mut TextureAtlasSprite,
-        &mut Transform,
-        &mut EnemyOne,
-    )>,
+    mut query: Query<
+        (
+            &mut Animator,
+            &TextureAtlasSprite,
+            &mut Transform,
+            &mut EnemyOne,
+            &StatusEffects,
+        ),
+        Without<Hitstun>,
+    >,
     time: Res<Time>,
-    beam_texture: Res<EnemyOneBeamSprite>,
 ) {
-    let frame = (time.time_since_startup().as_millis() / 100) % 7;
-
-    for (id, mut enemy_sprite, mut enemy_transform, mut enemy_one) in query.iter_mut() {
-        if enemy_one.state == EnemyOneState::Attack && frame % 7 == 6 {
+    for (mut animator, enemy_sprite, mut enemy_transform, mut enemy_one, effects) in
+        query.iter_mut()
+    {
+        if enemy_one.state == EnemyOneState::Attack && animator.finished() {
             enemy_one.state = EnemyOneState::Move;
 
             let mut rng = thread_rng();
@@ -248,26 +321,23 @@ fn animate_enemy_one(
             );
         }
 
-        enemy_one.attack_cooldown.tick(time.delta());
-        if enemy_one.attack_cooldown.finished()
-            && enemy_one.state == EnemyOneState::Idle
-            && frame == 0
-        {
+        enemy_one
+            .attack_cooldown
+            .tick(time.delta().mul_f32(effects.cooldown_rate()));
+        if enemy_one.attack_cooldown.finished() && enemy_one.state == EnemyOneState::Idle {
             enemy_one.state = EnemyOneState::Attack;
         }
 
-        let frame = match enemy_one.state {
-            EnemyOneState::Idle | EnemyOneState::Move => (frame + 1) % 7,
-            EnemyOneState::Attack => (frame % 7 + 7).min(11),
-        };
-
-        enemy_sprite.index = frame.try_into().expect("Should always fit into u128");
+        animator.play(match enemy_one.state {
+            EnemyOneState::Idle | EnemyOneState::Move => "idle",
+            EnemyOneState::Attack => "attack",
+        });
 
         const TOTAL_DISPLACEMENT: f32 = 1.0;
 
         let y_vel = match enemy_one.state {
             EnemyOneState::Idle | EnemyOneState::Move => {
-                if frame >= 2 {
+                if enemy_sprite.index >= 2 {
                     -TOTAL_DISPLACEMENT / 5.0
                 } else {
                     TOTAL_DISPLACEMENT / 2.0
@@ -276,20 +346,28 @@ fn animate_enemy_one(
             EnemyOneState::Attack => 0.0,
         };
 
-        if enemy_one.state == EnemyOneState::Attack && frame == 10 {
+        enemy_transform.translation.y += y_vel;
+    }
+}
+
+fn fire_enemy_one_beam(
+    mut commands: Commands,
+    mut events: EventReader<AnimationEvent>,
+    query: Query<&TextureAtlasSprite, (With<EnemyOne>, Without<Hitstun>)>,
+    beam_texture: Res<EnemyOneBeamSprite>,
+    mut shake: EventWriter<Shake>,
+) {
+    for event in events.iter().filter(|event| event.name == "beam") {
+        if let Ok(enemy_sprite) = query.get(event.entity) {
+            let direction = match enemy_sprite.flip_x {
+                true => 1.0,
+                false => -1.0,
+            };
             let beam = commands
                 .spawn_bundle(SpriteBundle {
                     texture: beam_texture.0.clone(),
                     transform: Transform {
-                        translation: Vec3::new(
-                            175.0
-                                * match enemy_sprite.flip_x {
-                                    true => 1.0,
-                                    false => -1.0,
-                                },
-                            1.5,
-                            20.0,
-                        ),
+                        translation: Vec3::new(175.0 * direction, 1.5, 20.0),
                         ..Default::default()
                     },
                     ..Default::default()
@@ -297,14 +375,14 @@ fn animate_enemy_one(
                 .insert(Collider::cuboid(167.0, 3.0))
                 .insert(Sensor)
                 .insert(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_STATIC)
-                .insert(ActiveEvents::COLLISION_EVENTS)
                 .insert(Beam::default())
+                .insert(enemy_one_beam_hitbox(event.entity, direction))
+                .insert(Team::Enemies)
                 .insert(Name::from("Beam"))
                 .id();
-            commands.entity(id).add_child(beam);
+            commands.entity(event.entity).add_child(beam);
+            shake.send(Shake(ENEMY_ONE_BEAM_TRAUMA));
         }
-
-        enemy_transform.translation.y += y_vel;
     }
 }
 
@@ -315,7 +393,12 @@ fn spawn_enemy_one(
 ) {
     let mut rng = thread_rng();
     for _ in 0..count.0 {
-        commands
+        let mut effects = StatusEffects::default();
+        if count.0 == MAX_ROLL {
+            effects.apply(MAX_ROLL_STUN);
+        }
+
+        let enemy = commands
             .spawn_bundle(SpriteSheetBundle {
                 sprite: TextureAtlasSprite::new(0),
                 texture_atlas: sprite_sheet.0.clone(),
@@ -329,11 +412,31 @@ fn spawn_enemy_one(
                 },
                 ..Default::default()
             })
+            // A floating body only so that knockback has a velocity to act on
+            .insert(RigidBody::Dynamic)
+            .insert(GravityScale(0.0))
+            .insert(LockedAxes::ROTATION_LOCKED)
+            .insert(Velocity::default())
+            .insert(Damping {
+                linear_damping: ENEMY_ONE_DAMPING,
+                angular_damping: 0.0,
+            })
             .insert(Collider::cuboid(10.5, 8.0))
             .insert(Sensor)
             .insert(ActiveCollisionTypes::default() | ActiveCollisionTypes::STATIC_STATIC)
+            .insert(Animator::new(vec![
+                AnimationClip::new("idle", [1, 2, 3, 4, 5, 6, 0], 10.0, AnimationMode::Loop),
+                AnimationClip::new(
+                    "attack",
+                    [7, 8, 9, 10, 11, 11, 11],
+                    10.0,
+                    AnimationMode::Once,
+                )
+                .with_event(3, "beam"),
+            ]))
             .insert(FacePlayer)
-            .insert(DiesToLaser)
+            .insert(Team::Enemies)
+            .insert(Hurtbox)
             .insert(EnemyOne {
                 state: EnemyOneState::Idle,
                 attack_cooldown: Timer::from_seconds(
@@ -342,10 +445,15 @@ fn spawn_enemy_one(
                     false,
                 ),
             })
-            .insert(Health {
-                health: ENEMY_ONE_HEALTH,
-            })
-            .insert(Name::from("EnemyOne"));
+            .insert(Health::new(ENEMY_ONE_HEALTH))
+            .insert(ENEMY_ONE_DAMAGE_RESPONSE)
+            .insert(effects)
+            .insert(Name::from("EnemyOne"))
+            .id();
+
+        if let Some(contact) = ENEMY_ONE_CONTACT_DAMAGE {
+            commands.entity(enemy).insert(contact);
+        }
     }
 }
 
@@ -1,35 +1,209 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashSet};
 use bevy_inspector_egui::Inspectable;
-use std::time::Duration;
+use bevy_rapier2d::prelude::*;
+use rand::{thread_rng, Rng};
+use std::{f32::consts::PI, time::Duration};
 
-use crate::{player::Player, ui::UpdatedHealth};
+use crate::{
+    camera::{HitStop, Shake},
+    particles::{ParticleConfig, ParticleEmitter, ParticleTexture},
+    physics::{Collision, CollisionApp},
+    player::Player,
+    status::{ApplyStatus, StatusApplication},
+    ui::{HeartsSheet, HEART_FULL, HEART_HALF, MAX_HEARTS_PER_ROW},
+    GameState,
+};
 
 pub struct HealthPlugin;
 
-#[derive(Component, Debug)]
-pub struct Damaged;
+const PLAYER_HIT_TRAUMA: f32 = 0.5;
+const KILL_TRAUMA: f32 = 0.4;
+const KILL_HIT_STOP_MS: u64 = 80;
+/// Sprite colours multiply the texture, so an overbright tint clamps every channel to white.
+const HIT_TINT: Color = Color::rgb(8.0, 8.0, 8.0);
+/// Killed enemies roll this die, dropping a whole heart on the top face and half of one on the
+/// face below. Rolling the top face twice running drops a heart container instead.
+const HEART_DROP_DIE: u8 = 6;
+const HEART_PICKUP_SCALE: f32 = 0.5;
+const HEART_CONTAINER_SCALE: f32 = 0.8;
+const HEART_CONTAINER_COLOR: Color = Color::rgb(1.0, 0.85, 0.3);
+/// Half-hearts that containers can raise a player to at most, as many as fit in a HUD row.
+const MAX_CONTAINER_HEALTH: u8 = MAX_HEARTS_PER_ROW * 2;
+
+/// What dealt a hit, so reactions can tell a laser from an enemy beam.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageKind {
+    Laser,
+    Beam,
+    /// Touching an enemy's body.
+    Contact,
+    /// Ticks from a status effect, with no entity behind them. They ignore i-frames and cause no
+    /// hit reaction.
+    Burn,
+}
+
+/// A request to hurt `target`. Anything that deals damage sends one of these and leaves health,
+/// invulnerability and death to `apply_damage`.
+#[derive(Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u8,
+    /// The entity that dealt the hit, such as the laser or beam.
+    pub source: Option<Entity>,
+    pub kind: DamageKind,
+    /// Added to the target's `Velocity`, in pixels per second.
+    pub knockback: Vec2,
+    /// Inflicted only if the hit lands.
+    pub status: Option<StatusApplication>,
+}
+
+/// Sent once when an entity's health reaches zero, just before it is despawned.
+#[derive(Debug)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub position: Vec3,
+    /// What dealt the killing blow, which picks the death effect.
+    pub kind: DamageKind,
+}
+
+/// Multiplies incoming damage, rounding to the nearest point. Hits are never scaled below one.
+#[derive(Component, Debug, Inspectable)]
+pub struct DamageScale(pub f32);
+
+/// Damage senders run before this so hits land the same frame.
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DamageSystem;
+/// Player health counts half-hearts, so `max` is twice the number of heart containers.
 #[derive(Component, Debug, Inspectable)]
 pub struct Health {
     pub health: u8,
+    pub max: u8,
+}
+
+impl Health {
+    pub fn new(max: u8) -> Self {
+        Self { health: max, max }
+    }
+
+    pub fn heal(&mut self, amount: u8) {
+        self.health = self.health.saturating_add(amount).min(self.max);
+    }
+}
+
+/// Restores `heal` half-hearts to the player that touches it, after raising their maximum by
+/// `containers` half-hearts.
+#[derive(Component)]
+pub struct HeartPickup {
+    heal: u8,
+    containers: u8,
+}
+
+impl HeartPickup {
+    /// Heals `health`, returning false and leaving it alone if the pickup would do nothing, so
+    /// it can be left for someone who can use it.
+    fn collect(&self, health: &mut Health) -> bool {
+        let room = MAX_CONTAINER_HEALTH.saturating_sub(health.max);
+        if health.health >= health.max && (self.containers == 0 || room == 0) {
+            return false;
+        }
+        health.max += self.containers.min(room);
+        health.heal(self.heal);
+        true
+    }
 }
+/// How an entity reacts to being hurt. Entities without one take damage with no i-frames.
+#[derive(Component, Clone, Debug)]
+pub struct DamageResponse {
+    pub invuln: Duration,
+    /// Time after a hit during which the entity can't act, so knockback isn't steered away.
+    pub hitstun: Duration,
+    pub flash: FlashPattern,
+    /// Time between the flash turning on and off.
+    pub flash_period: Duration,
+    pub sound: Option<&'static str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlashPattern {
+    None,
+    /// Fades the sprite almost out.
+    Blink,
+    /// Washes the sprite out to white.
+    Tint,
+}
+
+/// Set while an entity is reeling from a hit. Player input and enemy AI skip stunned entities.
+#[derive(Component, Debug)]
+pub struct Hitstun(Timer);
+
+impl Hitstun {
+    pub fn new(duration: Duration) -> Self {
+        Self(Timer::new(duration, false))
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct Invuln {
     duration: Timer,
     flash_period: Timer,
+    flash: FlashPattern,
+    /// Sprite colour from before the first flash, put back once the invulnerability ends.
+    original: Option<Color>,
+    lit: bool,
 }
 
-impl Default for Invuln {
-    fn default() -> Self {
+impl Invuln {
+    pub fn new(response: &DamageResponse) -> Self {
         Self {
-            duration: Timer::from_seconds(1.0, false),
-            flash_period: Timer::new(Duration::from_millis(200), true),
+            duration: Timer::new(response.invuln, false),
+            flash_period: Timer::new(response.flash_period, true),
+            flash: response.flash,
+            original: None,
+            lit: false,
         }
     }
+
+    /// The colour the sprite goes back to between flashes and once this ends.
+    pub fn original(&self) -> Option<Color> {
+        self.original
+    }
+
+    pub fn set_original(&mut self, color: Color) {
+        self.original = Some(color);
+    }
+
+    /// Whether the sprite is currently showing the flash rather than its own colour.
+    pub fn flashing(&self) -> bool {
+        self.lit && self.flash != FlashPattern::None
+    }
+
+    /// Invulnerability that leaves the sprite alone, for dodges rather than hits.
+    pub fn without_flash(duration: Duration) -> Self {
+        Self::new(&DamageResponse {
+            invuln: duration,
+            hitstun: Duration::ZERO,
+            flash: FlashPattern::None,
+            flash_period: duration,
+            sound: None,
+        })
+    }
 }
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(invuln).add_system(damaged);
+        app.add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_collision_pair::<HeartPickup, Player>()
+            // Frozen gameplay shouldn't eat into i-frames or stun
+            .add_system_set(
+                SystemSet::on_update(GameState::Play)
+                    .with_system(invuln)
+                    .with_system(hitstun),
+            )
+            .add_system(apply_damage.label(DamageSystem))
+            .add_system(death_effects.after(DamageSystem))
+            .add_system(drop_hearts.after(DamageSystem))
+            .add_system_set(SystemSet::on_update(GameState::Play).with_system(collect_hearts));
     }
 }
 
@@ -42,48 +216,390 @@ fn invuln(
         invuln.duration.tick(time.delta());
         invuln.flash_period.tick(time.delta());
 
-        // This is broken but ran out of time in jam
-        if invuln.flash_period.just_finished() {
-            let alpha = sprite.color.a();
-            sprite.color.set_a(match alpha {
-                0.1 => 0.0,
-                0.0 | 1.0 => 0.1,
-                _ => 1.0,
-            });
+        // Flash straight away rather than one period after the hit
+        if invuln.original.is_none() {
+            invuln.original = Some(sprite.color);
+            invuln.lit = true;
+            sprite.color = flash_color(invuln.flash, true, sprite.color);
         }
-        if invuln.duration.just_finished() {
-            sprite.color.set_a(1.0);
+        let original = invuln.original.unwrap_or(sprite.color);
+
+        if invuln.duration.finished() {
+            sprite.color = original;
             commands.entity(id).remove::<Invuln>();
+        } else if invuln.flash_period.just_finished() {
+            invuln.lit = !invuln.lit;
+            sprite.color = flash_color(invuln.flash, invuln.lit, original);
         }
     }
 }
 
-fn damaged(
+fn hitstun(mut commands: Commands, mut query: Query<(Entity, &mut Hitstun)>, time: Res<Time>) {
+    for (id, mut hitstun) in query.iter_mut() {
+        hitstun.0.tick(time.delta());
+        if hitstun.0.finished() {
+            commands.entity(id).remove::<Hitstun>();
+        }
+    }
+}
+
+fn flash_color(flash: FlashPattern, lit: bool, original: Color) -> Color {
+    match (flash, lit) {
+        (FlashPattern::Blink, true) => {
+            let mut faded = original;
+            faded.set_a(original.a() * 0.1);
+            faded
+        }
+        (FlashPattern::Tint, true) => HIT_TINT,
+        _ => original,
+    }
+}
+
+type DamageTargetQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Health,
+        &'static GlobalTransform,
+        Option<&'static DamageScale>,
+        Option<&'static DamageResponse>,
+        Option<&'static mut Velocity>,
+        Option<&'static Invuln>,
+    ),
+>;
+
+/// The hits `apply_damage` reads and everything it reports back.
+#[derive(SystemParam)]
+struct DamageEvents<'w, 's> {
+    hits: EventReader<'w, 's, DamageEvent>,
+    deaths: EventWriter<'w, 's, DeathEvent>,
+    shake: EventWriter<'w, 's, Shake>,
+    status: EventWriter<'w, 's, ApplyStatus>,
+}
+
+fn apply_damage(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Health), Added<Damaged>>,
-    player_query: Query<Entity, With<Player>>,
+    mut events: DamageEvents,
+    mut query: DamageTargetQuery,
+    player_query: Query<(), With<Player>>,
+    assets: Res<AssetServer>,
+    audio: Res<Audio>,
 ) {
-    for (id, mut health) in query.iter_mut() {
-        commands.entity(id).remove::<Damaged>();
-
-        // Very hacky, but running out of time
-        if player_query.get(id).is_ok() {
-            commands.entity(id).insert(Invuln::default());
-        } else {
-            commands.entity(id).insert(Invuln {
-                duration: Timer::new(Duration::from_millis(200), false),
-                flash_period: Timer::new(Duration::from_millis(40), false),
+    // Invuln is only inserted once commands apply, so one source can't land twice in a frame.
+    // Separate sources, such as scatter pellets arriving together, all count
+    let mut hit = HashSet::new();
+
+    for event in events.hits.iter() {
+        if event.source == Some(event.target) || !hit.insert((event.source, event.target)) {
+            continue;
+        }
+        let (mut health, global_transform, scale, response, velocity, invuln) =
+            match query.get_mut(event.target) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+        // Already killed by an earlier hit this frame, and despawned once commands apply
+        if health.health == 0 {
+            continue;
+        }
+        // Status ticks wear through i-frames and don't count as hits to react to
+        let status_tick = event.kind == DamageKind::Burn;
+        if invuln.is_some() && !status_tick {
+            continue;
+        }
+
+        let amount = match scale {
+            Some(DamageScale(scale)) => ((event.amount as f32 * scale).round() as u8).max(1),
+            None => event.amount,
+        };
+        health.health = health.health.saturating_sub(amount);
+
+        if let Some(mut velocity) = velocity {
+            velocity.linvel += event.knockback;
+        }
+
+        if let (Some(response), false) = (response, status_tick) {
+            if !response.invuln.is_zero() {
+                commands.entity(event.target).insert(Invuln::new(response));
+            }
+            if !response.hitstun.is_zero() {
+                commands
+                    .entity(event.target)
+                    .insert(Hitstun::new(response.hitstun));
+            }
+            if let Some(sound) = response.sound {
+                audio.play(assets.load(sound));
+            }
+        }
+
+        if player_query.get(event.target).is_ok() && !status_tick {
+            events.shake.send(Shake(PLAYER_HIT_TRAUMA));
+        }
+
+        if let (Some(hit_status), true) = (event.status, health.health > 0) {
+            events.status.send(ApplyStatus {
+                target: event.target,
+                status: hit_status,
             });
         }
 
-        health.health -= 1;
         if health.health == 0 {
-            commands.entity(id).despawn_recursive();
+            events.deaths.send(DeathEvent {
+                entity: event.target,
+                position: global_transform.translation(),
+                kind: event.kind,
+            });
+            commands.entity(event.target).despawn_recursive();
+        }
+    }
+}
+
+/// Runs in the same frame as `apply_damage`, before the dead entity's despawn is applied.
+fn death_effects(
+    mut commands: Commands,
+    mut deaths: EventReader<DeathEvent>,
+    player_query: Query<(), With<Player>>,
+    mut shake: EventWriter<Shake>,
+    mut hit_stop: EventWriter<HitStop>,
+) {
+    for death in deaths.iter() {
+        if player_query.get(death.entity).is_ok() {
+            continue;
+        }
+        shake.send(Shake(KILL_TRAUMA));
+        hit_stop.send(HitStop(Duration::from_millis(KILL_HIT_STOP_MS)));
+        spawn_death_burst(&mut commands, death.position, death.kind);
+    }
+}
+
+fn drop_hearts(
+    mut commands: Commands,
+    mut deaths: EventReader<DeathEvent>,
+    player_query: Query<(), With<Player>>,
+    heart_sheet: Res<HeartsSheet>,
+) {
+    let mut rng = thread_rng();
+    for death in deaths.iter() {
+        if player_query.get(death.entity).is_ok() {
+            continue;
         }
+        let (heal, containers) = match HEART_DROP_DIE - rng.gen_range(1..=HEART_DROP_DIE) {
+            0 if rng.gen_range(1..=HEART_DROP_DIE) == HEART_DROP_DIE => (2, 2),
+            0 => (2, 0),
+            1 => (1, 0),
+            _ => continue,
+        };
+        let (index, color, scale, name) = match (containers, heal) {
+            (0, 1) => (HEART_HALF, Color::WHITE, HEART_PICKUP_SCALE, "Heart Pickup"),
+            (0, _) => (HEART_FULL, Color::WHITE, HEART_PICKUP_SCALE, "Heart Pickup"),
+            _ => (
+                HEART_FULL,
+                HEART_CONTAINER_COLOR,
+                HEART_CONTAINER_SCALE,
+                "Heart Container Pickup",
+            ),
+        };
+
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index,
+                    color,
+                    ..Default::default()
+                },
+                texture_atlas: heart_sheet.0.clone(),
+                transform: Transform {
+                    translation: death.position.truncate().extend(90.0),
+                    scale: Vec3::splat(scale),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Collider::ball(8.0))
+            .insert(Sensor)
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .insert(HeartPickup { heal, containers })
+            .insert(Name::from(name));
+    }
+}
 
-        // Very hacky, but running out of time
-        if let Ok(player) = player_query.get(id) {
-            commands.entity(player).insert(UpdatedHealth);
+fn collect_hearts(
+    mut commands: Commands,
+    mut collisions: EventReader<Collision<HeartPickup, Player>>,
+    pickups: Query<&HeartPickup>,
+    mut players: Query<&mut Health, With<Player>>,
+) {
+    for collision in collisions.iter().filter(|collision| collision.started) {
+        if let (Ok(pickup), Ok(mut health)) =
+            (pickups.get(collision.a), players.get_mut(collision.b))
+        {
+            if pickup.collect(&mut health) {
+                commands.entity(collision.a).despawn_recursive();
+            }
         }
     }
 }
+
+fn spawn_death_burst(commands: &mut Commands, translation: Vec3, kind: DamageKind) {
+    // Burnt enemies go up in embers rather than blood
+    let (start_color, end_color) = match kind {
+        DamageKind::Burn => (
+            Color::rgb(1.0, 0.55, 0.15),
+            Color::rgba(0.3, 0.1, 0.05, 0.0),
+        ),
+        _ => (
+            Color::rgb(0.55, 0.05, 0.1),
+            Color::rgba(0.3, 0.0, 0.05, 0.0),
+        ),
+    };
+
+    commands
+        .spawn()
+        .insert_bundle(TransformBundle::from(Transform::from_translation(
+            translation,
+        )))
+        .insert(ParticleEmitter::burst(
+            ParticleConfig {
+                lifetime: 0.7,
+                velocity: Vec2::new(0.0, 80.0),
+                spread: PI,
+                speed_variance: 0.6,
+                gravity: 300.0,
+                start_color,
+                end_color,
+                size: Vec2::splat(2.0),
+                texture: ParticleTexture::None,
+            },
+            24,
+        ))
+        .insert(Name::from("Death Burst"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::StatusKind;
+    use bevy::asset::FileAssetIo;
+
+    fn damage_app() -> App {
+        let mut app = App::new();
+        app.add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<Shake>()
+            .add_event::<ApplyStatus>()
+            .insert_resource(AssetServer::new(FileAssetIo::new("assets", false)))
+            .insert_resource(Audio::<AudioSource>::default())
+            .add_system(apply_damage);
+        app
+    }
+
+    #[test]
+    fn simultaneous_lethal_hits_kill_once() {
+        let mut app = damage_app();
+        let target = app
+            .world
+            .spawn()
+            .insert(Health::new(2))
+            .insert(GlobalTransform::default())
+            .id();
+        let first = app.world.spawn().id();
+        let second = app.world.spawn().id();
+
+        let mut damage = app.world.resource_mut::<Events<DamageEvent>>();
+        for source in [first, second] {
+            damage.send(DamageEvent {
+                target,
+                amount: 2,
+                source: Some(source),
+                kind: DamageKind::Laser,
+                knockback: Vec2::ZERO,
+                status: None,
+            });
+        }
+        app.update();
+
+        let deaths = app.world.resource::<Events<DeathEvent>>();
+        assert_eq!(deaths.iter_current_update_events().count(), 1);
+        assert!(app.world.get_entity(target).is_none());
+    }
+
+    #[test]
+    fn status_is_inflicted_only_when_the_hit_lands() {
+        let mut app = damage_app();
+        let spawn_target = |app: &mut App| {
+            app.world
+                .spawn()
+                .insert(Health::new(4))
+                .insert(GlobalTransform::default())
+                .id()
+        };
+        let open = spawn_target(&mut app);
+        let blocking = spawn_target(&mut app);
+        app.world
+            .entity_mut(blocking)
+            .insert(Invuln::without_flash(Duration::from_secs(1)));
+        let source = app.world.spawn().id();
+
+        for target in [open, blocking] {
+            app.world.send_event(DamageEvent {
+                target,
+                amount: 1,
+                source: Some(source),
+                kind: DamageKind::Laser,
+                knockback: Vec2::ZERO,
+                status: Some(StatusApplication {
+                    kind: StatusKind::Slow,
+                    secs: 1.0,
+                }),
+            });
+        }
+        app.update();
+
+        let status = app.world.resource::<Events<ApplyStatus>>();
+        let targets: Vec<Entity> = status
+            .iter_current_update_events()
+            .map(|applied| applied.target)
+            .collect();
+        assert_eq!(targets, vec![open]);
+        assert_eq!(app.world.get::<Health>(blocking).unwrap().health, 4);
+    }
+
+    const HEART: HeartPickup = HeartPickup {
+        heal: 2,
+        containers: 0,
+    };
+    const CONTAINER: HeartPickup = HeartPickup {
+        heal: 2,
+        containers: 2,
+    };
+
+    #[test]
+    fn hearts_heal_up_to_max() {
+        let mut health = Health { health: 9, max: 10 };
+        assert!(HEART.collect(&mut health));
+        assert_eq!((health.health, health.max), (10, 10));
+
+        assert!(!HEART.collect(&mut health));
+    }
+
+    #[test]
+    fn containers_raise_max_until_the_cap() {
+        let mut health = Health::new(MAX_CONTAINER_HEALTH - 2);
+        assert!(CONTAINER.collect(&mut health));
+        assert_eq!(health.max, MAX_CONTAINER_HEALTH);
+        assert_eq!(health.health, MAX_CONTAINER_HEALTH);
+
+        assert!(!CONTAINER.collect(&mut health));
+        assert_eq!(health.max, MAX_CONTAINER_HEALTH);
+    }
+
+    #[test]
+    fn containers_at_the_cap_still_heal() {
+        let mut health = Health {
+            health: 1,
+            max: MAX_CONTAINER_HEALTH,
+        };
+        assert!(CONTAINER.collect(&mut health));
+        assert_eq!((health.health, health.max), (3, MAX_CONTAINER_HEALTH));
+    }
+}
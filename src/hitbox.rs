@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    health::{DamageEvent, DamageKind, DamageSystem, Invuln},
+    status::StatusApplication,
+    GameState,
+};
+
+pub struct HitboxPlugin;
+
+/// Which side an entity fights for. Hitboxes only hurt hurtboxes on the other team unless
+/// `FriendlyFire` says otherwise, so swapping a projectile's team turns it on its shooter.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Inspectable)]
+pub enum Team {
+    Players,
+    Enemies,
+}
+
+/// Whether each team's attacks can hurt their own side.
+#[derive(Default)]
+pub struct FriendlyFire {
+    pub players: bool,
+    pub enemies: bool,
+}
+
+impl FriendlyFire {
+    fn allows(&self, team: Team) -> bool {
+        match team {
+            Team::Players => self.players,
+            Team::Enemies => self.enemies,
+        }
+    }
+}
+
+/// Lets hitboxes of other teams damage the entity's `Health`.
+#[derive(Component)]
+pub struct Hurtbox;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Knockback {
+    /// The same push whatever is hit, such as along a laser's path.
+    Fixed(Vec2),
+    /// Away from the hitbox along x, and up.
+    Away(Vec2),
+}
+
+/// Damages the hurtboxes its collider overlaps, for as long as they overlap.
+#[derive(Component, Clone, Debug)]
+pub struct Hitbox {
+    pub damage: u8,
+    pub kind: DamageKind,
+    pub knockback: Knockback,
+    pub status: Option<StatusApplication>,
+    /// Targets left to pass through before the hitbox is despawned. `None` never runs out.
+    pub pierce: Option<u8>,
+    /// Whether it can hurt its own team when `FriendlyFire` allows. Bodies bumping into
+    /// teammates never should.
+    pub friendly_fire: bool,
+    /// The entity that fired it, which it never hurts.
+    pub owner: Option<Entity>,
+}
+
+impl Plugin for HitboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FriendlyFire>().add_system_set(
+            SystemSet::on_update(GameState::Play).with_system(hitbox_damage.before(DamageSystem)),
+        );
+    }
+}
+
+/// Hurtboxes that can be hit this frame.
+type HurtboxQuery<'w, 's> =
+    Query<'w, 's, (&'static Team, &'static GlobalTransform), (With<Hurtbox>, Without<Invuln>)>;
+
+fn hitbox_damage(
+    mut commands: Commands,
+    mut hitboxes: Query<(Entity, &mut Hitbox, &Team, &GlobalTransform)>,
+    hurtboxes: HurtboxQuery,
+    friendly_fire: Res<FriendlyFire>,
+    rapier: Res<RapierContext>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    for (hitbox_id, mut hitbox, team, transform) in hitboxes.iter_mut() {
+        for (a, b, intersecting) in rapier.intersections_with(hitbox_id) {
+            if !intersecting {
+                continue;
+            }
+            let target = match a == hitbox_id {
+                true => b,
+                false => a,
+            };
+            let (target_team, target_transform) = match hurtboxes.get(target) {
+                Ok(hurtbox) if hitbox.owner != Some(target) => hurtbox,
+                _ => continue,
+            };
+            if target_team == team && !(hitbox.friendly_fire && friendly_fire.allows(*team)) {
+                continue;
+            }
+
+            let knockback = match hitbox.knockback {
+                Knockback::Fixed(knockback) => knockback,
+                Knockback::Away(knockback) => {
+                    let direction =
+                        match target_transform.translation().x >= transform.translation().x {
+                            true => 1.0,
+                            false => -1.0,
+                        };
+                    knockback * Vec2::new(direction, 1.0)
+                }
+            };
+            damage.send(DamageEvent {
+                target,
+                amount: hitbox.damage,
+                source: Some(hitbox_id),
+                kind: hitbox.kind,
+                knockback,
+                status: hitbox.status,
+            });
+
+            match &mut hitbox.pierce {
+                Some(0) => {
+                    commands.entity(hitbox_id).despawn_recursive();
+                    break;
+                }
+                Some(pierce) => *pierce -= 1,
+                None => {}
+            }
+        }
+    }
+}
@@ -1,35 +1,67 @@
 use bevy::{prelude::*, render::texture::ImageSettings};
 use bevy_rapier2d::prelude::*;
 
+mod actions;
+mod animation;
+mod aseprite;
+mod camera;
 mod clouds;
 mod debug;
 mod enemy;
 mod health;
+mod hitbox;
+mod parallax;
+mod particles;
 mod physics;
 mod player;
+mod settings;
+mod status;
 mod ui;
+mod weapon;
 
+use actions::ActionsPlugin;
+use animation::AnimationPlugin;
+use aseprite::{AsepritePlugin, AsepriteSheet};
+use camera::{CameraPlugin, CameraShake};
 use clouds::CloudsPlugin;
 use debug::DebugPlugin;
 use enemy::EnemyPlugin;
 use health::HealthPlugin;
-use physics::PhysicsPlugin;
+use hitbox::HitboxPlugin;
+use parallax::ParallaxPlugin;
+use particles::ParticlePlugin;
+use physics::{PhysicsPlugin, Wall};
 use player::PlayerPlugin;
+use settings::SettingsPlugin;
+use status::StatusPlugin;
 use ui::UiPlugin;
+use weapon::WeaponPlugin;
 
 const RESOLUTION: f32 = 16.0 / 9.0;
 const PIXEL_WIDTH: f32 = 320.0;
 
 struct PlayerSheet(Handle<TextureAtlas>);
+struct PlayerAnimations(Handle<AsepriteSheet>);
 struct ArenaSprite(Handle<Image>);
 struct SkyboxSprite(Handle<Image>);
 struct BulletSprite(Handle<Image>);
+struct CasingSprite(Handle<Image>);
 struct GunSheet(Handle<TextureAtlas>);
+struct DashSound(Handle<AudioSource>);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub enum GameState {
     DiceRoll,
     Play,
+    HitStop,
+    Paused,
+}
+
+impl GameState {
+    /// States pushed on top of `Play` that should stop gameplay from advancing.
+    pub fn freezes_gameplay(&self) -> bool {
+        matches!(self, GameState::HitStop | GameState::Paused)
+    }
 }
 
 fn load_graphics(
@@ -37,17 +69,8 @@ fn load_graphics(
     assets: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    let image = assets.load("LittleGuy.png");
-    let atlas = TextureAtlas::from_grid_with_padding(
-        image,
-        Vec2::new(16.0, 21.0),
-        6,
-        2,
-        Vec2::splat(2.0),
-        Vec2::ZERO,
-    );
-    let atlas_handle = texture_atlases.add(atlas);
-    commands.insert_resource(PlayerSheet(atlas_handle));
+    commands.insert_resource(PlayerSheet(assets.load("LittleGuy.aseprite.json#atlas")));
+    commands.insert_resource(PlayerAnimations(assets.load("LittleGuy.aseprite.json")));
 
     let image = assets.load("Gun.png");
     let atlas = TextureAtlas::from_grid_with_padding(
@@ -67,13 +90,19 @@ fn load_graphics(
     commands.insert_resource(SkyboxSprite(image_handle));
     let image_handle = assets.load("Laser.png");
     commands.insert_resource(BulletSprite(image_handle));
+    let image_handle = assets.load("BulletCasing.png");
+    commands.insert_resource(CasingSprite(image_handle));
+}
+
+fn load_audio(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(DashSound(assets.load("SoundEffects/spin.wav")));
 }
 
 fn spawn_camera(mut commands: Commands) {
     let mut camera = Camera2dBundle::default();
     camera.projection.scale = 0.2;
 
-    commands.spawn_bundle(camera);
+    commands.spawn_bundle(camera).insert(CameraShake::default());
 }
 
 fn init_scene(
@@ -107,6 +136,7 @@ fn init_scene(
             translation: Vec3::new(-(PIXEL_WIDTH / 2.0 + 1.0), 0.0, 0.0),
             ..Default::default()
         })
+        .insert(Wall)
         .insert(Name::from("Left wall"));
 
     commands
@@ -117,6 +147,7 @@ fn init_scene(
             translation: Vec3::new(PIXEL_WIDTH / 2.0 + 1.0, 0.0, 0.0),
             ..Default::default()
         })
+        .insert(Wall)
         .insert(Name::from("Right wall"));
 
     commands
@@ -150,13 +181,24 @@ fn main() {
         })
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(450.0))
         .add_plugin(DebugPlugin)
+        .add_plugin(ActionsPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(ParallaxPlugin)
         .add_plugin(CloudsPlugin)
+        .add_plugin(ParticlePlugin)
+        .add_plugin(AnimationPlugin)
+        .add_plugin(AsepritePlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(WeaponPlugin)
         .add_plugin(PhysicsPlugin)
         .add_plugin(EnemyPlugin)
         .add_plugin(UiPlugin)
         .add_plugin(HealthPlugin)
+        .add_plugin(HitboxPlugin)
+        .add_plugin(StatusPlugin)
         .add_startup_system_to_stage(StartupStage::PreStartup, load_graphics)
+        .add_startup_system_to_stage(StartupStage::PreStartup, load_audio)
         .add_startup_system(spawn_camera)
         .add_startup_system(init_scene)
         .run();
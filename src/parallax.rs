@@ -0,0 +1,229 @@
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+pub struct ParallaxPlugin;
+
+/// An arena's background layers, loaded from a `.parallax.ron` file so they can be tweaked
+/// without a rebuild. Every layer is a tile of the one sprite sheet.
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "b3d7e1c2-5a4f-4e8b-8d19-7c2a6f0e4d31"]
+pub struct ParallaxBackground {
+    /// Path of the sheet image, relative to the `.parallax.ron` file.
+    image: String,
+    tile_size: Vec2,
+    columns: usize,
+    rows: usize,
+    #[serde(default)]
+    padding: Vec2,
+    pub layers: Vec<ParallaxLayerDef>,
+}
+
+/// Spawns the layers of a `ParallaxBackground` as children once it loads, and again whenever
+/// the file changes.
+#[derive(Component)]
+pub struct Parallax(pub Handle<ParallaxBackground>);
+
+/// Data describing a single scrolling background layer.
+#[derive(Clone, Deserialize)]
+pub struct ParallaxLayerDef {
+    pub name: String,
+    /// Filled in from the background's sheet when it loads.
+    #[serde(skip)]
+    pub atlas: Handle<TextureAtlas>,
+    pub index: usize,
+    /// Scroll speed in pixels per second.
+    pub speed: Vec2,
+    pub z: f32,
+    /// Size of one tile. Axes with a non-zero wrap are tiled and repeat seamlessly in either
+    /// direction; an axis left at zero scrolls off forever.
+    pub wrap: Vec2,
+    /// How much of the camera's movement the layer follows: `0.0` is fixed in the world, `1.0`
+    /// is glued to the screen.
+    pub camera_factor: f32,
+}
+
+#[derive(Component)]
+pub struct ParallaxLayer {
+    speed: Vec2,
+    wrap: Vec2,
+    camera_factor: f32,
+    scroll: Vec2,
+}
+
+impl ParallaxLayerDef {
+    pub fn spawn(&self, commands: &mut Commands) -> Entity {
+        let tiles = |wrap: f32| if wrap > 0.0 { -1..=1 } else { 0..=0 };
+
+        let mut layer_tiles = Vec::with_capacity(9);
+        for i in tiles(self.wrap.x) {
+            for j in tiles(self.wrap.y) {
+                layer_tiles.push(
+                    commands
+                        .spawn_bundle(SpriteSheetBundle {
+                            sprite: TextureAtlasSprite::new(self.index),
+                            texture_atlas: self.atlas.clone(),
+                            transform: Transform {
+                                translation: Vec3::new(
+                                    self.wrap.x * i as f32,
+                                    self.wrap.y * j as f32,
+                                    0.0,
+                                ),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .insert(Name::from(format!("{}_{i}_{j}", self.name)))
+                        .id(),
+                );
+            }
+        }
+
+        commands
+            .spawn()
+            .insert(GlobalTransform::default())
+            .insert(Transform::from_xyz(0.0, 0.0, self.z))
+            .insert(ParallaxLayer {
+                speed: self.speed,
+                wrap: self.wrap,
+                camera_factor: self.camera_factor,
+                scroll: Vec2::ZERO,
+            })
+            .insert(Name::from(self.name.clone()))
+            .push_children(&layer_tiles)
+            .id()
+    }
+}
+
+#[derive(Default)]
+struct ParallaxLoader;
+
+impl AssetLoader for ParallaxLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut background: ParallaxBackground = ron::de::from_bytes(bytes)?;
+
+            let image_path = load_context
+                .path()
+                .parent()
+                .map(|dir| dir.join(&background.image))
+                .unwrap_or_else(|| background.image.clone().into());
+            let image_path = AssetPath::new(image_path, None);
+            let image = load_context.get_handle(image_path.clone());
+
+            let atlas = TextureAtlas::from_grid_with_padding(
+                image,
+                background.tile_size,
+                background.columns,
+                background.rows,
+                background.padding,
+                Vec2::ZERO,
+            );
+            let atlas = load_context
+                .set_labeled_asset("atlas", LoadedAsset::new(atlas).with_dependency(image_path));
+            for layer in background.layers.iter_mut() {
+                layer.atlas = atlas.clone();
+            }
+
+            load_context.set_default_asset(LoadedAsset::new(background));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["parallax.ron"]
+    }
+}
+
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ParallaxBackground>()
+            .init_asset_loader::<ParallaxLoader>()
+            .add_system(spawn_parallax)
+            .add_system(scroll_parallax);
+    }
+}
+
+fn spawn_parallax(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<ParallaxBackground>>,
+    query: Query<(Entity, &Parallax)>,
+    backgrounds: Res<Assets<ParallaxBackground>>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        let background = match backgrounds.get(handle) {
+            Some(background) => background,
+            None => continue,
+        };
+
+        for (id, _) in query.iter().filter(|(_, parallax)| parallax.0 == *handle) {
+            let layers: Vec<Entity> = background
+                .layers
+                .iter()
+                .map(|layer| layer.spawn(&mut commands))
+                .collect();
+            let mut parent = commands.entity(id);
+            parent.despawn_descendants();
+            parent.push_children(&layers);
+        }
+    }
+}
+
+fn wrap(value: f32, period: f32) -> f32 {
+    if period > 0.0 {
+        value.rem_euclid(period)
+    } else {
+        value
+    }
+}
+
+fn scroll_parallax(
+    mut query: Query<(&mut Transform, &mut ParallaxLayer)>,
+    camera_query: Query<&Transform, (With<Camera2d>, Without<ParallaxLayer>)>,
+    time: Res<Time>,
+) {
+    let camera = camera_query
+        .get_single()
+        .map(|transform| transform.translation.truncate())
+        .unwrap_or_default();
+
+    for (mut transform, mut layer) in query.iter_mut() {
+        let speed = layer.speed;
+        layer.scroll += speed * time.delta_seconds();
+
+        let offset = Vec2::new(
+            wrap(layer.scroll.x, layer.wrap.x),
+            wrap(layer.scroll.y, layer.wrap.y),
+        ) + camera * layer.camera_factor;
+
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_background_parses() {
+        let background: ParallaxBackground =
+            ron::from_str(include_str!("../assets/Arena.parallax.ron")).unwrap();
+
+        assert_eq!(background.image, "SeparateClouds.png");
+        assert_eq!(background.layers.len(), 3);
+        assert_eq!(background.layers[0].speed, Vec2::new(-5.0, 0.0));
+    }
+}
@@ -0,0 +1,251 @@
+use bevy::{prelude::*, transform::TransformSystem};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+
+use crate::GameState;
+
+pub struct ParticlePlugin;
+
+#[derive(Clone)]
+pub enum ParticleTexture {
+    /// Plain coloured quad of `ParticleConfig::size`.
+    None,
+    Image(Handle<Image>),
+    /// Frames are stepped through evenly over each particle's lifetime.
+    Atlas {
+        atlas: Handle<TextureAtlas>,
+        frames: Vec<usize>,
+        flip_x: bool,
+    },
+}
+
+/// Describes how every particle from an emitter looks and moves.
+#[derive(Clone)]
+pub struct ParticleConfig {
+    pub lifetime: f32,
+    pub velocity: Vec2,
+    /// Maximum angle in radians that `velocity` is randomly rotated by, either way.
+    pub spread: f32,
+    /// Fraction of the speed that is randomly added or removed.
+    pub speed_variance: f32,
+    pub gravity: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub size: Vec2,
+    pub texture: ParticleTexture,
+}
+
+#[derive(Component)]
+pub struct ParticleEmitter {
+    pub config: ParticleConfig,
+    /// Particles per second.
+    pub rate: f32,
+    /// Particles emitted all at once on the emitter's first update.
+    pub burst: u32,
+    /// The emitter despawns once this finishes. `None` emits until removed.
+    pub duration: Option<Timer>,
+    accumulator: f32,
+}
+
+impl ParticleEmitter {
+    pub fn continuous(config: ParticleConfig, rate: f32) -> Self {
+        Self {
+            config,
+            rate,
+            burst: 0,
+            duration: None,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Emits `count` particles once and then despawns.
+    pub fn burst(config: ParticleConfig, count: u32) -> Self {
+        Self {
+            config,
+            rate: 0.0,
+            burst: count,
+            duration: Some(Timer::from_seconds(0.0, false)),
+            accumulator: 0.0,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    gravity: f32,
+    age: Timer,
+    start_color: Color,
+    end_color: Color,
+    frames: Vec<usize>,
+}
+
+/// Particles are plain or atlas sprites, so only one of the two is ever present.
+type ParticleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static mut Particle,
+        Option<&'static mut Sprite>,
+        Option<&'static mut TextureAtlasSprite>,
+    ),
+>;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        // Emitters read `GlobalTransform`, so wait for propagation to place freshly spawned ones
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            emit_particles.after(TransformSystem::TransformPropagate),
+        )
+        .add_system(update_particles);
+    }
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let [r1, g1, b1, a1] = from.as_rgba_f32();
+    let [r2, g2, b2, a2] = to.as_rgba_f32();
+    Color::rgba(
+        r1 + (r2 - r1) * t,
+        g1 + (g2 - g1) * t,
+        b1 + (b2 - b1) * t,
+        a1 + (a2 - a1) * t,
+    )
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    config: &ParticleConfig,
+    translation: Vec3,
+    rng: &mut ThreadRng,
+) {
+    let angle = match config.spread > 0.0 {
+        true => rng.gen_range(-config.spread..=config.spread),
+        false => 0.0,
+    };
+    let speed = match config.speed_variance > 0.0 {
+        true => 1.0 + rng.gen_range(-config.speed_variance..=config.speed_variance),
+        false => 1.0,
+    };
+    let velocity = Mat2::from_angle(angle) * config.velocity * speed;
+    let transform = Transform::from_translation(translation);
+
+    let mut particle = match &config.texture {
+        ParticleTexture::None => commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: config.start_color,
+                custom_size: Some(config.size),
+                ..Default::default()
+            },
+            transform,
+            ..Default::default()
+        }),
+        ParticleTexture::Image(image) => commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: config.start_color,
+                ..Default::default()
+            },
+            texture: image.clone(),
+            transform,
+            ..Default::default()
+        }),
+        ParticleTexture::Atlas {
+            atlas,
+            frames,
+            flip_x,
+        } => commands.spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                color: config.start_color,
+                index: frames.first().copied().unwrap_or_default(),
+                flip_x: *flip_x,
+                ..Default::default()
+            },
+            texture_atlas: atlas.clone(),
+            transform,
+            ..Default::default()
+        }),
+    };
+
+    particle.insert(Particle {
+        velocity,
+        gravity: config.gravity,
+        age: Timer::from_seconds(config.lifetime, false),
+        start_color: config.start_color,
+        end_color: config.end_color,
+        frames: match &config.texture {
+            ParticleTexture::Atlas { frames, .. } => frames.clone(),
+            _ => Vec::new(),
+        },
+    });
+}
+
+fn emit_particles(
+    mut commands: Commands,
+    mut query: Query<(Entity, &GlobalTransform, &mut ParticleEmitter)>,
+    state: Res<State<GameState>>,
+    time: Res<Time>,
+) {
+    if state.current().freezes_gameplay() {
+        return;
+    }
+
+    let mut rng = thread_rng();
+    for (id, transform, mut emitter) in query.iter_mut() {
+        emitter.accumulator += emitter.rate * time.delta_seconds();
+        let count = std::mem::take(&mut emitter.burst) + emitter.accumulator as u32;
+        emitter.accumulator = emitter.accumulator.fract();
+
+        for _ in 0..count {
+            spawn_particle(
+                &mut commands,
+                &emitter.config,
+                transform.translation(),
+                &mut rng,
+            );
+        }
+
+        if let Some(duration) = &mut emitter.duration {
+            duration.tick(time.delta());
+            if duration.finished() {
+                commands.entity(id).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    mut query: ParticleQuery,
+    state: Res<State<GameState>>,
+    time: Res<Time>,
+) {
+    if state.current().freezes_gameplay() {
+        return;
+    }
+
+    for (id, mut transform, mut particle, sprite, atlas_sprite) in query.iter_mut() {
+        particle.age.tick(time.delta());
+        if particle.age.finished() {
+            commands.entity(id).despawn_recursive();
+            continue;
+        }
+
+        particle.velocity.y -= particle.gravity * time.delta_seconds();
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+
+        let life = particle.age.percent();
+        let color = lerp_color(particle.start_color, particle.end_color, life);
+
+        if let Some(mut sprite) = sprite {
+            sprite.color = color;
+        }
+        if let Some(mut sprite) = atlas_sprite {
+            sprite.color = color;
+            if !particle.frames.is_empty() {
+                let frame = (life * particle.frames.len() as f32) as usize;
+                sprite.index = particle.frames[frame.min(particle.frames.len() - 1)];
+            }
+        }
+    }
+}
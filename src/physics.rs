@@ -1,6 +1,12 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
 use bevy_rapier2d::prelude::*;
+use std::{f32::consts::FRAC_PI_3, marker::PhantomData};
+
+use crate::{
+    particles::{ParticleConfig, ParticleEmitter, ParticleTexture},
+    GameState,
+};
 
 pub struct PhysicsPlugin;
 
@@ -17,11 +23,65 @@ pub struct GroundSensor {
 #[derive(Component, Debug)]
 pub struct Ground;
 
+/// Which sides of the entity are touching a `Wall`. The entity's collider must be a cuboid.
+#[derive(Component, Inspectable, Default)]
+pub struct WallDetection {
+    pub left: bool,
+    pub right: bool,
+}
+
+#[derive(Component)]
+pub struct WallSensor {
+    pub wall_detection_entity: Entity,
+    /// `-1.0` for the left side, `1.0` for the right.
+    pub side: f32,
+}
+
+#[derive(Component, Debug)]
+pub struct Wall;
+
+/// Sent when a `GroundDetection` entity goes from airborne to grounded.
+pub struct Landed(pub Entity);
+
+/// A `CollisionEvent` between an entity with `A` and one with `B`, always in that order. Register
+/// the pair with `App::add_collision_pair` and read these rather than matching raw events.
+pub struct Collision<A, B> {
+    pub a: Entity,
+    pub b: Entity,
+    /// `false` when the two stop touching.
+    pub started: bool,
+    marker: PhantomData<fn() -> (A, B)>,
+}
+
+pub trait CollisionApp {
+    /// Sends a `Collision<A, B>` for every collision between the two. Each pair should only be
+    /// added once. Events are sent in `CoreStage::PreUpdate`, so any `Update` system can read
+    /// them the same frame.
+    fn add_collision_pair<A: Component, B: Component>(&mut self) -> &mut Self;
+}
+
+impl CollisionApp for App {
+    fn add_collision_pair<A: Component, B: Component>(&mut self) -> &mut Self {
+        self.add_event::<Collision<A, B>>()
+            .add_system_to_stage(CoreStage::PreUpdate, dispatch_collisions::<A, B>)
+    }
+}
+
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_ground_collider)
+        app.add_event::<Landed>()
+            .add_collision_pair::<GroundSensor, Ground>()
+            .add_collision_pair::<WallSensor, Wall>()
+            .add_startup_system(spawn_ground_collider)
             .add_system(detect_ground)
-            .add_system(spawn_ground_sensor);
+            .add_system(spawn_ground_sensor)
+            .add_system(detect_walls)
+            .add_system(spawn_wall_sensors)
+            .add_system(landing_dust.after(detect_ground))
+            .add_system_set(SystemSet::on_enter(GameState::HitStop).with_system(pause_physics))
+            .add_system_set(SystemSet::on_exit(GameState::HitStop).with_system(resume_physics))
+            .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(pause_physics))
+            .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(resume_physics));
     }
 }
 
@@ -34,46 +94,60 @@ fn spawn_ground_collider(mut commands: Commands) {
         .insert(Name::from("Ground Collider"));
 }
 
+fn pause_physics(mut rapier: ResMut<RapierConfiguration>) {
+    rapier.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier: ResMut<RapierConfiguration>) {
+    rapier.physics_pipeline_active = true;
+}
+
+fn dispatch_collisions<A: Component, B: Component>(
+    mut collisions: EventReader<CollisionEvent>,
+    a_query: Query<(), With<A>>,
+    b_query: Query<(), With<B>>,
+    mut events: EventWriter<Collision<A, B>>,
+) {
+    for collision in collisions.iter() {
+        let (first, second, started) = match collision {
+            CollisionEvent::Started(a, b, _) => (*a, *b, true),
+            CollisionEvent::Stopped(a, b, _) => (*a, *b, false),
+        };
+
+        // Rapier doesn't promise an order, so try both
+        let (a, b) = if a_query.get(first).is_ok() && b_query.get(second).is_ok() {
+            (first, second)
+        } else if a_query.get(second).is_ok() && b_query.get(first).is_ok() {
+            (second, first)
+        } else {
+            continue;
+        };
+
+        events.send(Collision {
+            a,
+            b,
+            started,
+            marker: PhantomData,
+        });
+    }
+}
+
 fn detect_ground(
     sensors: Query<&GroundSensor>,
-    mut collisions: EventReader<CollisionEvent>,
+    mut collisions: EventReader<Collision<GroundSensor, Ground>>,
     mut entities: Query<&mut GroundDetection>,
-    grounds: Query<&Ground>,
+    mut landed: EventWriter<Landed>,
 ) {
     for collision in collisions.iter() {
-        match collision {
-            CollisionEvent::Started(a, b, _) => {
-                // TODO: Inspect order for entities in `CollisionEvent`, as it always seems to be
-                // the sensor in `b`
-                if let Ok(sensor) = sensors.get(*b) {
-                    if let Ok(mut entity) = entities.get_mut(sensor.ground_detection_entity) {
-                        if grounds.get(*a).is_ok() {
-                            entity.grounded = true;
-                        }
-                    }
-                } else if let Ok(sensor) = sensors.get(*a) {
-                    if let Ok(mut entity) = entities.get_mut(sensor.ground_detection_entity) {
-                        if grounds.get(*b).is_ok() {
-                            entity.grounded = true;
-                        }
-                    }
-                }
-            }
-            CollisionEvent::Stopped(a, b, _) => {
-                if let Ok(sensor) = sensors.get(*b) {
-                    if let Ok(mut entity) = entities.get_mut(sensor.ground_detection_entity) {
-                        if grounds.get(*a).is_ok() {
-                            entity.grounded = false;
-                        }
-                    } else if let Ok(sensor) = sensors.get(*a) {
-                        if let Ok(mut entity) = entities.get_mut(sensor.ground_detection_entity) {
-                            if grounds.get(*b).is_ok() {
-                                entity.grounded = false;
-                            }
-                        }
-                    }
-                }
+        let sensor = match sensors.get(collision.a) {
+            Ok(sensor) => sensor,
+            Err(_) => continue,
+        };
+        if let Ok(mut entity) = entities.get_mut(sensor.ground_detection_entity) {
+            if collision.started && !entity.grounded {
+                landed.send(Landed(sensor.ground_detection_entity));
             }
+            entity.grounded = collision.started;
         }
     }
 }
@@ -108,3 +182,93 @@ fn spawn_ground_sensor(
         commands.entity(id).add_child(sensor);
     }
 }
+
+fn detect_walls(
+    sensors: Query<&WallSensor>,
+    mut collisions: EventReader<Collision<WallSensor, Wall>>,
+    mut entities: Query<&mut WallDetection>,
+) {
+    for collision in collisions.iter() {
+        let sensor = match sensors.get(collision.a) {
+            Ok(sensor) => sensor,
+            Err(_) => continue,
+        };
+        if let Ok(mut entity) = entities.get_mut(sensor.wall_detection_entity) {
+            match sensor.side < 0.0 {
+                true => entity.left = collision.started,
+                false => entity.right = collision.started,
+            }
+        }
+    }
+}
+
+fn spawn_wall_sensors(
+    mut commands: Commands,
+    query: Query<(Entity, &Collider), Added<WallDetection>>,
+) {
+    const SENSOR_WIDTH: f32 = 1.0;
+
+    for (id, collider) in query.iter() {
+        let collider_extents = collider
+            .as_cuboid()
+            .expect("All WallDetection entities should use a cuboid collider")
+            .half_extents();
+
+        for side in [-1.0, 1.0] {
+            // Kept short of the feet so that standing on the floor doesn't count as a wall
+            let sensor = commands
+                .spawn()
+                .insert(WallSensor {
+                    wall_detection_entity: id,
+                    side,
+                })
+                .insert(Collider::cuboid(SENSOR_WIDTH, collider_extents[1] * 0.8))
+                .insert(Sensor)
+                .insert(ActiveEvents::COLLISION_EVENTS)
+                .insert(Transform {
+                    translation: Vec3::new(side * (collider_extents[0] + SENSOR_WIDTH), 0.0, 0.0),
+                    ..Default::default()
+                })
+                .insert(Name::from("Wall Sensor"))
+                .id();
+
+            commands.entity(id).add_child(sensor);
+        }
+    }
+}
+
+fn landing_dust(
+    mut commands: Commands,
+    mut landed: EventReader<Landed>,
+    query: Query<(&GlobalTransform, &Collider)>,
+) {
+    for Landed(id) in landed.iter() {
+        if let Ok((transform, collider)) = query.get(*id) {
+            let half_height = collider
+                .as_cuboid()
+                .map(|cuboid| cuboid.half_extents()[1])
+                .unwrap_or_default();
+
+            commands
+                .spawn()
+                .insert_bundle(TransformBundle::from(Transform::from_translation(
+                    transform.translation() - Vec3::new(0.0, half_height, 0.0),
+                )))
+                .insert(ParticleEmitter::burst(
+                    ParticleConfig {
+                        lifetime: 0.4,
+                        velocity: Vec2::new(0.0, 25.0),
+                        spread: FRAC_PI_3,
+                        speed_variance: 0.5,
+                        gravity: 40.0,
+                        start_color: Color::rgba(0.8, 0.75, 0.7, 0.8),
+                        end_color: Color::rgba(0.8, 0.75, 0.7, 0.0),
+                        size: Vec2::splat(2.0),
+                        texture: ParticleTexture::None,
+                    },
+                    6,
+                ))
+                .insert(Name::from("Landing Dust"));
+        }
+    }
+}
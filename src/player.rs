@@ -1,20 +1,63 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
 use bevy_rapier2d::prelude::*;
-use std::{f32::consts::PI, time::Duration};
-
-use crate::enemy::Beam;
-use crate::health::{Damaged, Health, Invuln};
-use crate::physics::{Ground, GroundDetection};
-use crate::ui::UpdatedHealth;
-use crate::{BulletSprite, GameState, GunSheet, PlayerSheet};
+use std::time::Duration;
+
+use crate::actions::{Action, ActionState, InputMap};
+use crate::animation::{AnimationSystem, Animator};
+use crate::aseprite::AsepriteAnimation;
+use crate::health::{DamageKind, DamageResponse, FlashPattern, Health, Hitstun, Invuln};
+use crate::hitbox::{Hitbox, Hurtbox, Knockback, Team};
+use crate::particles::{ParticleConfig, ParticleEmitter, ParticleTexture};
+use crate::physics::{GroundDetection, Landed, WallDetection};
+use crate::settings::Bindings;
+use crate::status::StatusEffects;
+use crate::weapon::{Weapon, WEAPONS};
+use crate::{
+    BulletSprite, CasingSprite, DashSound, GameState, GunSheet, PlayerAnimations, PlayerSheet,
+};
 
 pub struct PlayerPlugin;
 
+const MAX_PLAYERS: usize = 2;
+const PLAYER_COLORS: [Color; MAX_PLAYERS] = [Color::WHITE, Color::rgb(0.6, 0.8, 1.0)];
+/// Collision group that keeps players from bumping into each other.
+const PLAYER_GROUP: u32 = 0b10;
+/// Five hearts, counted in halves.
+const PLAYER_HEALTH: u8 = 10;
+const PLAYER_DAMAGE_RESPONSE: DamageResponse = DamageResponse {
+    invuln: Duration::from_millis(1000),
+    hitstun: Duration::from_millis(250),
+    flash: FlashPattern::Blink,
+    flash_period: Duration::from_millis(100),
+    sound: Some("SoundEffects/damageTaken.wav"),
+};
 const PLAYER_SPEED: f32 = 100.0;
-const GUN_COOLDOWN_MS: u64 = 100;
-const GUN_TRAVEL_SPEED: f32 = 300.0;
-const DEG2RAD: f32 = PI / 180.0;
+const PLAYER_ACCELERATION: f32 = 900.0;
+const PLAYER_DECELERATION: f32 = 1200.0;
+const PLAYER_AIR_CONTROL: f32 = 0.6;
+const COYOTE_TIME_SECS: f32 = 0.1;
+const JUMP_BUFFER_SECS: f32 = 0.12;
+const JUMP_CUT: f32 = 0.5;
+const POGO_STRENGTH: f32 = 180.0;
+const POGO_CHARGES: u8 = 2;
+const WALL_SLIDE_SPEED: f32 = 40.0;
+const WALL_JUMP_PUSH: f32 = 150.0;
+const DASH_SPEED: f32 = 320.0;
+const DASH_MS: u64 = 150;
+const DASH_COOLDOWN_MS: u64 = 800;
+const DASH_INVULN_MS: u64 = 250;
+const AFTERIMAGE_RATE: f32 = 60.0;
+const GUN_PIVOT: Vec2 = Vec2::new(0.0, -4.5);
+const GUN_RADIUS: f32 = 7.5;
+/// Cosine of the steepest slope the player can stand on.
+const MAX_SLOPE_COS: f32 = 0.7;
+const SLOPE_PROBE: f32 = 4.0;
+const MAX_STEP_HEIGHT: f32 = 4.0;
+const STEP_PROBE: f32 = 2.0;
+const STEP_SPEED: f32 = 80.0;
+/// How close to straight down a shot has to be to give recoil.
+const DOWNWARD_AIM: f32 = 0.9;
 
 #[derive(Inspectable)]
 enum PlayerAnimState {
@@ -25,59 +68,135 @@ enum PlayerAnimState {
 
 #[derive(Component, Inspectable)]
 pub struct Player {
+    /// Index into `PlayerSlots`, which also picks the player's colour and HUD corner.
+    pub id: usize,
     speed: f32,
+    /// Pixels per second squared gained towards `speed` while moving.
+    acceleration: f32,
+    /// Pixels per second squared lost while not moving.
+    deceleration: f32,
+    /// Fraction of acceleration and deceleration available in the air.
+    air_control: f32,
     jump_force: f32,
+    /// Seconds after walking off a ledge that a jump is still allowed.
+    coyote_time: f32,
+    /// Seconds a jump pressed before landing is remembered for.
+    jump_buffer: f32,
+    /// Fraction of upward velocity kept when jump is released early.
+    jump_cut: f32,
     anim_state: PlayerAnimState,
 }
 
+#[derive(Component)]
+pub struct Dash {
+    pub cooldown: Timer,
+    duration: Timer,
+    direction: f32,
+}
+
+impl Default for Dash {
+    fn default() -> Self {
+        // Both timers start finished so the first dash is available straight away
+        let mut cooldown = Timer::new(Duration::from_millis(DASH_COOLDOWN_MS), false);
+        cooldown.set_elapsed(cooldown.duration());
+        let mut duration = Timer::new(Duration::from_millis(DASH_MS), false);
+        duration.set_elapsed(duration.duration());
+
+        Self {
+            cooldown,
+            duration,
+            direction: 1.0,
+        }
+    }
+}
+
+/// Shooting straight down kicks the player upwards. Only `charges` kicks are available in the
+/// air, refilled on landing; kicks from the ground are free.
+#[derive(Component, Inspectable)]
+pub struct Pogo {
+    /// Upward speed added by each kick, in pixels per second.
+    pub strength: f32,
+    pub max_charges: u8,
+    pub charges: u8,
+}
+
+#[derive(Component)]
+struct JumpState {
+    since_grounded: f32,
+    since_pressed: f32,
+    /// Set from take-off until the player starts falling.
+    rising: bool,
+}
+
+impl Default for JumpState {
+    fn default() -> Self {
+        Self {
+            since_grounded: f32::INFINITY,
+            since_pressed: f32::INFINITY,
+            rising: false,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Gun {
+    pub weapon: &'static Weapon,
     timer: Timer,
 }
 
+impl Gun {
+    pub fn new(weapon: &'static Weapon) -> Self {
+        Self {
+            weapon,
+            timer: Timer::new(weapon.cooldown, true),
+        }
+    }
+
+    pub fn equip(&mut self, weapon: &'static Weapon) {
+        *self = Gun::new(weapon);
+    }
+}
+
 #[derive(Component)]
 pub struct GunNozzle;
 
-#[derive(Component, Debug)]
-pub struct Laser {
-    direction: Dir,
+/// Unit vector the player's gun points along.
+#[derive(Component, Inspectable)]
+pub struct Aim {
+    pub direction: Vec2,
+    /// Switched on by moving the mouse and off by any aim action.
+    using_mouse: bool,
 }
 
-#[derive(Debug)]
-enum Dir {
-    Left,
-    Right,
-    Down,
+#[derive(Component, Debug)]
+pub struct Laser {
+    pub velocity: Vec2,
 }
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_player).add_system_set(
-            SystemSet::on_update(GameState::Play)
-                .with_system(animate_player)
-                .with_system(player_movement)
-                .with_system(gun_position)
-                .with_system(shoot_gun)
-                .with_system(damage_from_beam)
-                .with_system(bullet_travel),
-        );
+        app.add_startup_system(spawn_first_player)
+            .add_system(join_player)
+            .add_system_set(SystemSet::on_enter(GameState::DiceRoll).with_system(revive_players))
+            .add_system_set(
+                SystemSet::on_update(GameState::Play)
+                    .with_system(animate_player.before(AnimationSystem))
+                    .with_system(player_movement)
+                    .with_system(player_dash.after(player_movement))
+                    .with_system(update_aim.after(player_movement))
+                    .with_system(gun_position.after(update_aim))
+                    .with_system(shoot_gun.after(update_aim))
+                    .with_system(refill_pogo)
+                    .with_system(slow_players.before(player_movement))
+                    .with_system(bullet_travel),
+            );
     }
 }
 
-fn damage_from_beam(
-    beam_query: Query<&Beam>,
-    mut collisions: EventReader<CollisionEvent>,
-    mut commands: Commands,
-    player: Query<Entity, (With<Player>, Without<Invuln>)>,
-) {
-    for collision in collisions.iter() {
-        if let CollisionEvent::Started(a, b, _) = collision {
-            if player.get(*a).is_ok() && beam_query.get(*b).is_ok() {
-                commands.entity(*a).insert(Damaged);
-            } else if beam_query.get(*a).is_ok() && player.get(*b).is_ok() {
-                commands.entity(*b).insert(Damaged);
-            }
-        }
+/// Recomputed only while effects change, so the inspector can still tune speed otherwise.
+fn slow_players(mut query: Query<(&mut Player, &StatusEffects), Changed<StatusEffects>>) {
+    for (mut player, effects) in query.iter_mut() {
+        player.speed = PLAYER_SPEED * effects.speed_multiplier();
     }
 }
 
@@ -87,12 +206,7 @@ fn bullet_travel(
     time: Res<Time>,
 ) {
     for (id, mut transform, laser) in query.iter_mut() {
-        transform.translation += time.delta_seconds()
-            * match laser.direction {
-                Dir::Left => Vec3::new(-GUN_TRAVEL_SPEED, 0.0, 0.0),
-                Dir::Right => Vec3::new(GUN_TRAVEL_SPEED, 0.0, 0.0),
-                Dir::Down => Vec3::new(0.0, -GUN_TRAVEL_SPEED, 0.0),
-            };
+        transform.translation += (laser.velocity * time.delta_seconds()).extend(0.0);
 
         if transform.translation.x.abs() > 180.0 || transform.translation.y.abs() > 100.0 {
             commands.entity(id).despawn_recursive();
@@ -100,98 +214,349 @@ fn bullet_travel(
     }
 }
 
+type ShooterQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static ActionState,
+        &'static Aim,
+        &'static GroundDetection,
+        &'static mut Pogo,
+        &'static mut JumpState,
+        &'static mut Velocity,
+        &'static mut GravityScale,
+        Option<&'static Hitstun>,
+    ),
+    With<Player>,
+>;
+
 fn shoot_gun(
     mut commands: Commands,
-    keyboard: Res<Input<KeyCode>>,
-    mut query: Query<(
-        &mut Gun,
-        &mut TextureAtlasSprite,
-        &mut Transform,
-        &GlobalTransform,
-    )>,
+    mut player_query: ShooterQuery,
+    mut query: Query<(&Parent, &mut Gun, &mut TextureAtlasSprite, &GlobalTransform)>,
     time: Res<Time>,
     laser_sprite: Res<BulletSprite>,
+    casing_sprite: Res<CasingSprite>,
 ) {
-    let holding_down = keyboard.just_pressed(KeyCode::Down) || keyboard.pressed(KeyCode::S);
-    let (mut gun, mut gun_sprite, mut gun_transform, gun_transform_global) = query.single_mut();
+    for (parent, mut gun, mut gun_sprite, gun_transform_global) in query.iter_mut() {
+        let (actions, aim, ground_sensor, mut pogo, mut jump, mut vel, mut gravity, hitstun) =
+            match player_query.get_mut(parent.get()) {
+                Ok(player) => player,
+                Err(_) => continue,
+            };
+        let shooting_down = aim.direction.y <= -DOWNWARD_AIM;
+
+        let weapon = gun.weapon;
+        gun_sprite.index = weapon.sprite.idle;
+        gun_sprite.color = weapon.sprite.color;
+
+        gun.timer.tick(time.delta());
+
+        if gun.timer.just_finished() && actions.pressed(Action::Fire) && hitstun.is_none() {
+            gun_sprite.index = weapon.sprite.firing;
+
+            for direction in weapon.shot_directions(aim.direction) {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: weapon.sprite.color,
+                            ..Default::default()
+                        },
+                        texture: laser_sprite.0.clone(),
+                        transform: Transform {
+                            translation: gun_transform_global.translation()
+                                + Vec3::new(0.0, 1.0, -1.0),
+                            rotation: Quat::from_rotation_z(direction.y.atan2(direction.x)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(Collider::cuboid(5.5, 1.5))
+                    .insert(Sensor)
+                    .insert(Laser {
+                        velocity: direction * weapon.speed,
+                    })
+                    .insert(Hitbox {
+                        damage: weapon.damage,
+                        kind: DamageKind::Laser,
+                        knockback: Knockback::Fixed(direction * weapon.knockback),
+                        status: weapon.status,
+                        pierce: Some(weapon.piercing),
+                        friendly_fire: true,
+                        owner: Some(parent.get()),
+                    })
+                    .insert(Team::Players);
+            }
 
-    gun_sprite.index = 0;
+            if shooting_down && (ground_sensor.grounded || pogo.charges > 0) {
+                if !ground_sensor.grounded {
+                    pogo.charges -= 1;
+                }
+                // Cancel any fall so every kick gains the same height
+                vel.linvel.y = vel.linvel.y.max(0.0) + pogo.strength;
+                jump.rising = true;
+                *gravity = GravityScale(1.0);
+            }
 
-    gun.timer.tick(time.delta());
+            // Casings are thrown up and back over the player's shoulder
+            let backwards = match gun_sprite.flip_x {
+                true => 1.0,
+                false => -1.0,
+            };
+            commands
+                .spawn()
+                .insert_bundle(TransformBundle::from(Transform::from_translation(
+                    gun_transform_global.translation() + Vec3::new(0.0, 1.0, 1.0),
+                )))
+                .insert(ParticleEmitter::burst(
+                    ParticleConfig {
+                        lifetime: 0.5,
+                        velocity: Vec2::new(backwards * 40.0, 90.0),
+                        spread: 0.3,
+                        speed_variance: 0.2,
+                        gravity: 500.0,
+                        start_color: Color::WHITE,
+                        end_color: Color::rgba(1.0, 1.0, 1.0, 0.0),
+                        size: Vec2::ONE,
+                        texture: ParticleTexture::Image(casing_sprite.0.clone()),
+                    },
+                    1,
+                ))
+                .insert(Name::from("Casing"));
+        }
+    }
+}
 
-    if holding_down {
-        gun_transform.rotation = Quat::from_rotation_z(
-            90.0 * DEG2RAD
-                * match gun_sprite.flip_x {
-                    true => 1.0,
-                    false => -1.0,
-                },
-        );
-    } else {
-        gun_transform.rotation = Quat::default();
+fn refill_pogo(mut landed: EventReader<Landed>, mut query: Query<&mut Pogo>) {
+    for Landed(id) in landed.iter() {
+        if let Ok(mut pogo) = query.get_mut(*id) {
+            pogo.charges = pogo.max_charges;
+        }
     }
+}
 
-    if gun.timer.just_finished() && keyboard.pressed(KeyCode::Space) {
-        gun_sprite.index = 1;
-
-        let laser = commands
-            .spawn_bundle(SpriteBundle {
-                texture: laser_sprite.0.clone(),
-                transform: Transform {
-                    translation: gun_transform_global.translation() + Vec3::new(0.0, 1.0, -1.0),
-                    rotation: match holding_down {
-                        false => Quat::default(),
-                        true => Quat::from_rotation_z(90.0 * DEG2RAD),
-                    },
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Collider::cuboid(5.5, 1.5))
-            .insert(Sensor)
-            .insert(ActiveEvents::COLLISION_EVENTS)
-            .insert(Laser {
-                direction: match holding_down {
-                    false => match gun_sprite.flip_x {
-                        true => Dir::Left,
-                        false => Dir::Right,
-                    },
-                    true => Dir::Down,
-                },
-            })
-            .id();
+fn cursor_world_position(
+    windows: &Windows,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+
+    let window_size = Vec2::new(window.width(), window.height());
+    let ndc = (cursor / window_size) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+
+    Some(ndc_to_world.project_point3(ndc.extend(-1.0)).truncate())
+}
+
+fn update_aim(
+    mut query: Query<
+        (
+            &ActionState,
+            &InputMap,
+            &mut Aim,
+            &mut TextureAtlasSprite,
+            &GlobalTransform,
+        ),
+        With<Player>,
+    >,
+    camera_query: Query<(&Camera, &GlobalTransform), Without<Player>>,
+    windows: Res<Windows>,
+    mut cursor_moved: EventReader<CursorMoved>,
+) {
+    let mouse_moved = cursor_moved.iter().count() > 0;
+
+    for (actions, map, mut aim, mut sprite, transform) in query.iter_mut() {
+        let stick = Vec2::new(
+            actions.value(Action::AimRight) - actions.value(Action::AimLeft),
+            actions.value(Action::AimUp) - actions.value(Action::AimDown),
+        );
+        if mouse_moved && map.keyboard_mouse {
+            aim.using_mouse = true;
+        }
+        if stick != Vec2::ZERO {
+            aim.using_mouse = false;
+        }
+
+        let facing = match sprite.flip_x {
+            true => -1.0,
+            false => 1.0,
+        };
+        let moving = actions.pressed(Action::MoveLeft) || actions.pressed(Action::MoveRight);
+
+        let direction = if aim.using_mouse {
+            camera_query
+                .get_single()
+                .ok()
+                .and_then(|(camera, camera_transform)| {
+                    cursor_world_position(&windows, camera, camera_transform)
+                })
+                .map(|cursor| cursor - transform.translation().truncate())
+        } else if stick.x != 0.0 {
+            Some(stick)
+        } else if stick.y != 0.0 {
+            // Digital up aims diagonally while running. Down always aims straight down so a
+            // running player can still pogo
+            let x = match moving && stick.y > 0.0 {
+                true => facing,
+                false => 0.0,
+            };
+            Some(Vec2::new(x, stick.y))
+        } else {
+            None
+        };
+
+        aim.direction = direction
+            .and_then(Vec2::try_normalize)
+            .unwrap_or_else(|| Vec2::new(facing, 0.0));
 
-        if holding_down {
-            commands.entity(laser).insert(Ground);
+        if aim.direction.x != 0.0 {
+            sprite.flip_x = aim.direction.x < 0.0;
         }
     }
 }
 
+type GunHolderQuery<'w, 's> =
+    Query<'w, 's, (&'static TextureAtlasSprite, &'static Aim), (With<Player>, Without<Gun>)>;
+
 fn gun_position(
-    mut gun_query: Query<(&mut Transform, &mut TextureAtlasSprite), With<Gun>>,
-    player_query: Query<&TextureAtlasSprite, (With<Player>, Without<Gun>)>,
+    mut gun_query: Query<(&Parent, &mut Transform, &mut TextureAtlasSprite), With<Gun>>,
+    player_query: GunHolderQuery,
+) {
+    for (parent, mut gun_transform, mut gun_sprite) in gun_query.iter_mut() {
+        let (player_sprite, aim) = match player_query.get(parent.get()) {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+
+        gun_sprite.flip_x = player_sprite.flip_x;
+
+        // The gun orbits the player's hands, and a flipped sprite points along -x
+        let z = gun_transform.translation.z;
+        gun_transform.translation = (GUN_PIVOT + aim.direction * GUN_RADIUS).extend(z);
+        gun_transform.rotation = Quat::from_rotation_z(match gun_sprite.flip_x {
+            true => (-aim.direction.y).atan2(-aim.direction.x),
+            false => aim.direction.y.atan2(aim.direction.x),
+        });
+    }
+}
+
+/// The devices each joined player is controlled with, indexed by `Player::id`. Players stay
+/// joined after dying and are revived at the next dice roll.
+struct PlayerSlots(Vec<PlayerSlot>);
+
+#[derive(Clone, Copy)]
+struct PlayerSlot {
+    keyboard_mouse: bool,
+    gamepad: Option<Gamepad>,
+}
+
+fn spawn_first_player(
+    mut commands: Commands,
+    sprite_sheet: Res<PlayerSheet>,
+    animations: Res<PlayerAnimations>,
+    gun_sheet: Res<GunSheet>,
+    bindings: Res<Bindings>,
+) {
+    let slot = PlayerSlot {
+        keyboard_mouse: true,
+        gamepad: Some(Gamepad::new(0)),
+    };
+    spawn_player(
+        &mut commands,
+        0,
+        slot,
+        &sprite_sheet,
+        &animations,
+        &gun_sheet,
+        &bindings,
+    );
+    commands.insert_resource(PlayerSlots(vec![slot]));
+}
+
+/// A second player joins by pressing South on a gamepad nobody is using.
+fn join_player(
+    mut commands: Commands,
+    mut slots: ResMut<PlayerSlots>,
+    buttons: Res<Input<GamepadButton>>,
+    sprite_sheet: Res<PlayerSheet>,
+    animations: Res<PlayerAnimations>,
+    gun_sheet: Res<GunSheet>,
+    bindings: Res<Bindings>,
 ) {
-    let (mut gun_transform, mut gun_sprite) = gun_query.single_mut();
-    let player_sprite = player_query.single();
+    if slots.0.len() >= MAX_PLAYERS {
+        return;
+    }
 
-    if player_sprite.flip_x {
-        gun_transform.translation.x = -7.5;
-    } else {
-        gun_transform.translation.x = 7.5;
+    let gamepad = buttons
+        .get_just_pressed()
+        .filter(|button| button.button_type == GamepadButtonType::South)
+        .map(|button| button.gamepad)
+        .find(|gamepad| slots.0.iter().all(|slot| slot.gamepad != Some(*gamepad)));
+
+    if let Some(gamepad) = gamepad {
+        let slot = PlayerSlot {
+            keyboard_mouse: false,
+            gamepad: Some(gamepad),
+        };
+        spawn_player(
+            &mut commands,
+            slots.0.len(),
+            slot,
+            &sprite_sheet,
+            &animations,
+            &gun_sheet,
+            &bindings,
+        );
+        slots.0.push(slot);
     }
+}
 
-    gun_sprite.flip_x = player_sprite.flip_x;
+fn revive_players(
+    mut commands: Commands,
+    slots: Res<PlayerSlots>,
+    players: Query<&Player>,
+    sprite_sheet: Res<PlayerSheet>,
+    animations: Res<PlayerAnimations>,
+    gun_sheet: Res<GunSheet>,
+    bindings: Res<Bindings>,
+) {
+    for (id, slot) in slots.0.iter().enumerate() {
+        if players.iter().all(|player| player.id != id) {
+            spawn_player(
+                &mut commands,
+                id,
+                *slot,
+                &sprite_sheet,
+                &animations,
+                &gun_sheet,
+                &bindings,
+            );
+        }
+    }
 }
 
-fn spawn_player(mut commands: Commands, sprite_sheet: Res<PlayerSheet>, gun_sheet: Res<GunSheet>) {
-    let sprite = TextureAtlasSprite::new(0);
+fn spawn_player(
+    commands: &mut Commands,
+    id: usize,
+    slot: PlayerSlot,
+    sprite_sheet: &PlayerSheet,
+    animations: &PlayerAnimations,
+    gun_sheet: &GunSheet,
+    bindings: &Bindings,
+) {
+    let sprite = TextureAtlasSprite {
+        color: PLAYER_COLORS[id],
+        ..Default::default()
+    };
 
     let player = commands
         .spawn_bundle(SpriteSheetBundle {
             sprite,
             texture_atlas: sprite_sheet.0.clone(),
             transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 100.0),
+                translation: Vec3::new(id as f32 * 24.0, 0.0, 100.0),
                 ..Default::default()
             },
             ..Default::default()
@@ -201,15 +566,51 @@ fn spawn_player(mut commands: Commands, sprite_sheet: Res<PlayerSheet>, gun_shee
         .insert(Velocity::default())
         .insert(GravityScale::default())
         .insert(Collider::cuboid(8.0, 10.5))
+        .insert(CollisionGroups::new(PLAYER_GROUP, !PLAYER_GROUP))
+        // Without friction the player would stick to walls they run into mid-air
+        .insert(Friction {
+            coefficient: 0.0,
+            combine_rule: CoefficientCombineRule::Min,
+        })
+        .insert(Ccd::enabled())
         .insert(Player {
+            id,
             speed: PLAYER_SPEED,
+            acceleration: PLAYER_ACCELERATION,
+            deceleration: PLAYER_DECELERATION,
+            air_control: PLAYER_AIR_CONTROL,
             jump_force: 200.0,
+            coyote_time: COYOTE_TIME_SECS,
+            jump_buffer: JUMP_BUFFER_SECS,
+            jump_cut: JUMP_CUT,
             anim_state: PlayerAnimState::Idle,
         })
-        .insert(Health { health: 5 })
-        .insert(UpdatedHealth)
+        .insert(AsepriteAnimation(animations.0.clone()))
+        .insert(Health::new(PLAYER_HEALTH))
+        .insert(PLAYER_DAMAGE_RESPONSE)
+        .insert(Team::Players)
+        .insert(Hurtbox)
+        .insert(StatusEffects::default())
         .insert(GroundDetection::default())
-        .insert(Name::new("Player"))
+        .insert(WallDetection::default())
+        .insert(JumpState::default())
+        .insert(Dash::default())
+        .insert(Pogo {
+            strength: POGO_STRENGTH,
+            max_charges: POGO_CHARGES,
+            charges: POGO_CHARGES,
+        })
+        .insert(Aim {
+            direction: Vec2::X,
+            using_mouse: false,
+        })
+        .insert(InputMap {
+            bindings: bindings.0.clone(),
+            keyboard_mouse: slot.keyboard_mouse,
+            gamepad: slot.gamepad,
+        })
+        .insert(ActionState::default())
+        .insert(Name::new(format!("Player {}", id + 1)))
         .id();
 
     let gun = commands
@@ -222,9 +623,7 @@ fn spawn_player(mut commands: Commands, sprite_sheet: Res<PlayerSheet>, gun_shee
             },
             ..Default::default()
         })
-        .insert(Gun {
-            timer: Timer::new(Duration::from_millis(GUN_COOLDOWN_MS), true),
-        })
+        .insert(Gun::new(&WEAPONS[0]))
         .insert(Name::from("Gun"))
         .id();
 
@@ -243,74 +642,278 @@ fn spawn_player(mut commands: Commands, sprite_sheet: Res<PlayerSheet>, gun_shee
     commands.entity(player).add_child(gun);
 }
 
-fn player_movement(
-    mut player_query: Query<(
-        &mut Player,
-        &mut Transform,
-        &mut TextureAtlasSprite,
-        &mut Velocity,
-        &GroundDetection,
-        &mut GravityScale,
-    )>,
-    keyboard: Res<Input<KeyCode>>,
-    time: Res<Time>,
-) {
-    let (mut player, mut transform, mut sprite, mut vel, ground_sensor, mut gravity) =
-        player_query.single_mut();
+fn move_towards(current: f32, target: f32, max_delta: f32) -> f32 {
+    match (target - current).abs() <= max_delta {
+        true => target,
+        false => current + (target - current).signum() * max_delta,
+    }
+}
 
-    let right = keyboard.pressed(KeyCode::D) || keyboard.pressed(KeyCode::Right);
-    let left = keyboard.pressed(KeyCode::A) || keyboard.pressed(KeyCode::Left);
-    let up = keyboard.pressed(KeyCode::W) || keyboard.pressed(KeyCode::Up);
-    let down = keyboard.pressed(KeyCode::S) || keyboard.pressed(KeyCode::Down);
+type PlayerMoveQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Player,
+        &'static Transform,
+        &'static Collider,
+        &'static mut TextureAtlasSprite,
+        &'static mut Velocity,
+        &'static GroundDetection,
+        &'static WallDetection,
+        &'static mut GravityScale,
+        &'static mut JumpState,
+        &'static ActionState,
+        Option<&'static Hitstun>,
+    ),
+>;
+
+fn player_movement(mut player_query: PlayerMoveQuery, rapier: Res<RapierContext>, time: Res<Time>) {
+    for (
+        id,
+        mut player,
+        transform,
+        collider,
+        mut sprite,
+        mut vel,
+        ground_sensor,
+        walls,
+        mut gravity,
+        mut jump,
+        actions,
+        hitstun,
+    ) in player_query.iter_mut()
+    {
+        // Stunned players ignore their input, but held buttons aren't pressed afresh once it ends
+        let stunned = hitstun.is_some();
+        let input = match stunned {
+            true => 0.0,
+            false => actions.value(Action::MoveRight) - actions.value(Action::MoveLeft),
+        };
+        let dt = time.delta_seconds();
+
+        // Turning around brakes as well as accelerates so changing direction feels snappy
+        let mut rate = match input != 0.0 {
+            true => player.acceleration,
+            false => player.deceleration,
+        };
+        if input != 0.0 && vel.linvel.x != 0.0 && input.signum() != vel.linvel.x.signum() {
+            rate += player.deceleration;
+        }
+        if !ground_sensor.grounded || jump.rising {
+            rate *= player.air_control;
+        }
+        // Let knockback carry a stunned player rather than braking against it
+        if !stunned {
+            vel.linvel.x = move_towards(vel.linvel.x, input * player.speed, rate * dt);
+        }
 
-    let mut delta_x = 0.0;
-    if right {
-        delta_x += player.speed * time.delta_seconds();
-    }
-    if left {
-        delta_x -= player.speed * time.delta_seconds();
-    }
+        if input != 0.0 {
+            player.anim_state = PlayerAnimState::Run;
 
-    if delta_x != 0.0 {
-        player.anim_state = PlayerAnimState::Run;
+            sprite.flip_x = input < 0.0;
+        } else {
+            player.anim_state = PlayerAnimState::Idle;
+        }
 
-        sprite.flip_x = delta_x < 0.0;
-    } else {
-        player.anim_state = PlayerAnimState::Idle;
-    }
+        let half_extents = collider
+            .as_cuboid()
+            .map(|cuboid| cuboid.half_extents())
+            .unwrap_or_default();
+        let position = transform.translation.truncate();
+        let feet = position.y - half_extents.y;
+        // Players pass through each other, so the probes shouldn't see them either
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_rigid_body(id)
+            .groups(InteractionGroups::new(u32::MAX, !PLAYER_GROUP));
+
+        let slope_normal = rapier
+            .cast_ray_and_get_normal(
+                position,
+                -Vec2::Y,
+                half_extents.y + SLOPE_PROBE,
+                true,
+                filter,
+            )
+            .map(|(_, hit)| hit.normal)
+            .filter(|normal| normal.y >= MAX_SLOPE_COS && normal.y < 1.0);
+
+        // The ground sensor lags a frame behind take-off, so rising counts as airborne
+        let grounded = ground_sensor.grounded && !jump.rising;
+        match grounded {
+            true => jump.since_grounded = 0.0,
+            false => jump.since_grounded += dt,
+        }
+        match actions.just_pressed(Action::Jump) && !stunned {
+            true => jump.since_pressed = 0.0,
+            false => jump.since_pressed += dt,
+        }
+
+        let buffered = jump.since_pressed <= player.jump_buffer;
+        let coyote = jump.since_grounded <= player.coyote_time;
+        let wall_side = match (walls.left, walls.right) {
+            (true, _) => -1.0,
+            (_, true) => 1.0,
+            _ => 0.0,
+        };
+        let wall_jump = buffered && !coyote && wall_side != 0.0;
+
+        if (buffered && coyote) || wall_jump {
+            // A buffered press may already have been let go of, which makes for a short hop
+            vel.linvel.y = match actions.pressed(Action::Jump) {
+                true => player.jump_force,
+                false => player.jump_force * player.jump_cut,
+            };
+            if wall_jump {
+                vel.linvel.x = -wall_side * WALL_JUMP_PUSH;
+            }
+            jump.since_grounded = f32::INFINITY;
+            jump.since_pressed = f32::INFINITY;
+            jump.rising = true;
+            *gravity = GravityScale(1.0);
+        } else if jump.rising {
+            if actions.just_released(Action::Jump) && !stunned && vel.linvel.y > 0.0 {
+                vel.linvel.y *= player.jump_cut;
+            }
+            if vel.linvel.y <= 0.0 {
+                jump.rising = false;
+            }
+        }
+
+        if !grounded || jump.rising {
+            player.anim_state = PlayerAnimState::Jump;
 
-    if !ground_sensor.grounded {
-        player.anim_state = PlayerAnimState::Jump;
+            if vel.linvel[1] < 0.0 {
+                *gravity = GravityScale(1.5);
+            }
 
-        if vel.linvel[1] < 0.0 {
-            *gravity = GravityScale(1.5);
+            // Pushing into a wall while falling slides down it slowly
+            if wall_side != 0.0 && input.signum() == wall_side {
+                vel.linvel.y = vel.linvel.y.max(-WALL_SLIDE_SPEED);
+            }
+        } else if let Some(normal) = slope_normal {
+            // Run along the slope instead of launching off it, and don't slide down it when idle
+            vel.linvel.y = -vel.linvel.x * normal.x / normal.y;
+            *gravity = GravityScale(0.0);
+        } else {
+            *gravity = GravityScale(1.0);
+
+            // Climb small ledges rather than stopping dead against them
+            if input != 0.0 {
+                let forward = Vec2::new(input.signum(), 0.0);
+                let reach = half_extents.x + STEP_PROBE;
+                let blocked = |height: f32| {
+                    rapier
+                        .cast_ray(
+                            Vec2::new(position.x, feet + height),
+                            forward,
+                            reach,
+                            true,
+                            filter,
+                        )
+                        .is_some()
+                };
+
+                if blocked(1.0) && !blocked(MAX_STEP_HEIGHT + 1.0) {
+                    vel.linvel.y = vel.linvel.y.max(STEP_SPEED);
+                }
+            }
         }
-    } else if up || (down && keyboard.pressed(KeyCode::Space)) {
-        vel.linvel = Vec2::new(0.0, player.jump_force);
-    } else {
-        *gravity = GravityScale(1.0);
     }
-
-    transform.translation += Vec3::new(delta_x, 0.0, 0.0);
 }
 
-fn animate_player(
-    mut player_query: Query<(&Player, &mut TextureAtlasSprite, &Velocity)>,
+type DashQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Player,
+        &'static mut Dash,
+        &'static mut Velocity,
+        &'static mut GravityScale,
+        &'static TextureAtlasSprite,
+        &'static Handle<TextureAtlas>,
+        &'static ActionState,
+        Option<&'static Invuln>,
+        Option<&'static Hitstun>,
+    ),
+>;
+
+fn player_dash(
+    mut commands: Commands,
+    mut query: DashQuery,
+    audio: Res<Audio>,
+    sound: Res<DashSound>,
     time: Res<Time>,
 ) {
-    let (player, mut sprite, velocity) = player_query.single_mut();
-
-    sprite.index = match player.anim_state {
-        PlayerAnimState::Idle => 0,
-        PlayerAnimState::Run => ((time.time_since_startup().as_millis() / 100) % 5 + 1)
-            .try_into()
-            .expect("Spritesheet index should always fit into usize!"),
-        PlayerAnimState::Jump => {
-            if velocity.linvel[1] >= 0.0 {
-                6
-            } else {
-                7
+    for (id, player, mut dash, mut vel, mut gravity, sprite, atlas, actions, invuln, hitstun) in
+        query.iter_mut()
+    {
+        dash.cooldown.tick(time.delta());
+        dash.duration.tick(time.delta());
+
+        if actions.just_pressed(Action::Dash) && dash.cooldown.finished() && hitstun.is_none() {
+            let input = actions.value(Action::MoveRight) - actions.value(Action::MoveLeft);
+            dash.direction = match (input != 0.0, sprite.flip_x) {
+                (true, _) => input.signum(),
+                (false, true) => -1.0,
+                (false, false) => 1.0,
+            };
+            dash.cooldown.reset();
+            dash.duration.reset();
+
+            audio.play(sound.0.clone());
+            // Don't cut short the longer invulnerability from being hit
+            if invuln.is_none() {
+                commands
+                    .entity(id)
+                    .insert(Invuln::without_flash(Duration::from_millis(DASH_INVULN_MS)));
             }
+            commands.entity(id).insert(ParticleEmitter::continuous(
+                ParticleConfig {
+                    lifetime: 0.2,
+                    velocity: Vec2::ZERO,
+                    spread: 0.0,
+                    speed_variance: 0.0,
+                    gravity: 0.0,
+                    start_color: Color::rgba(0.6, 0.8, 1.0, 0.6),
+                    end_color: Color::rgba(0.6, 0.8, 1.0, 0.0),
+                    size: Vec2::ONE,
+                    texture: ParticleTexture::Atlas {
+                        atlas: atlas.clone(),
+                        frames: vec![sprite.index],
+                        flip_x: sprite.flip_x,
+                    },
+                },
+                AFTERIMAGE_RATE,
+            ));
         }
-    };
+
+        if !dash.duration.finished() {
+            vel.linvel = Vec2::new(dash.direction * DASH_SPEED, 0.0);
+            *gravity = GravityScale(0.0);
+        } else if dash.duration.just_finished() {
+            vel.linvel.x = dash.direction * player.speed;
+            *gravity = GravityScale(1.0);
+            commands.entity(id).remove::<ParticleEmitter>();
+        }
+    }
+}
+
+fn animate_player(mut player_query: Query<(&Player, &mut Animator, &Velocity)>) {
+    // The animator is attached once the sprite sheet has loaded, so not every player matches yet
+    for (player, mut animator, velocity) in player_query.iter_mut() {
+        animator.play(match player.anim_state {
+            PlayerAnimState::Idle => "idle",
+            PlayerAnimState::Run => "run",
+            PlayerAnimState::Jump => {
+                if velocity.linvel[1] >= 0.0 {
+                    "jump"
+                } else {
+                    "fall"
+                }
+            }
+        });
+    }
 }
@@ -0,0 +1,445 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::PathBuf};
+
+use crate::{
+    actions::{default_bindings, Action, ActionState, Binding, InputMap},
+    camera::Accessibility,
+    ui::UiFont,
+    GameState,
+};
+
+pub struct SettingsPlugin;
+
+const FONT_SIZE: f32 = 24.0;
+const BINDINGS_FILE: &str = "bindings.ron";
+const ACCESSIBILITY_FILE: &str = "accessibility.ron";
+/// Bindings are listed first, then the accessibility toggles.
+const MENU_ROWS: usize = Action::ALL.len() + Toggle::ALL.len();
+
+/// The bindings every `InputMap` is built from, persisted in the user's config directory.
+pub struct Bindings(pub Vec<(Action, Binding)>);
+
+impl Bindings {
+    /// Another action that `binding` already drives, which would be ambiguous to share.
+    fn conflict(&self, action: Action, binding: Binding) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(other, bound)| *bound == binding && *other != action)
+            .map(|(other, _)| *other)
+    }
+
+    /// Replaces `action`'s binding on the same device as `binding`, leaving other devices bound.
+    fn rebind(&mut self, action: Action, binding: Binding) {
+        self.0.retain(|(bound_action, bound)| {
+            *bound_action != action || !bound.same_device(&binding)
+        });
+        self.0.push((action, binding));
+    }
+}
+
+/// An `Accessibility` switch listed under the bindings.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Toggle {
+    ScreenShake,
+    HitStop,
+}
+
+impl Toggle {
+    const ALL: [Toggle; 2] = [Toggle::ScreenShake, Toggle::HitStop];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Toggle::ScreenShake => "Shake",
+            Toggle::HitStop => "Hit stop",
+        }
+    }
+
+    fn enabled(&self, accessibility: &Accessibility) -> bool {
+        match self {
+            Toggle::ScreenShake => accessibility.screen_shake,
+            Toggle::HitStop => accessibility.hit_stop,
+        }
+    }
+
+    fn flip(&self, accessibility: &mut Accessibility) {
+        match self {
+            Toggle::ScreenShake => accessibility.screen_shake ^= true,
+            Toggle::HitStop => accessibility.hit_stop ^= true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ControlsMenu {
+    selected: usize,
+    awaiting_input: bool,
+    message: String,
+}
+
+impl ControlsMenu {
+    fn selected_row(&self) -> MenuRow {
+        match Action::ALL.get(self.selected) {
+            Some(action) => MenuRow::Binding(*action),
+            None => MenuRow::Toggle(Toggle::ALL[self.selected - Action::ALL.len()]),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ControlsScreen;
+/// A line of the pause screen, in the order `ControlsMenu::selected` counts them.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum MenuRow {
+    Binding(Action),
+    Toggle(Toggle),
+}
+#[derive(Component)]
+struct MenuMessage;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlsMenu>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_bindings)
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_accessibility)
+            .add_system(toggle_pause.before(controls_menu))
+            .add_system(apply_bindings)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused).with_system(spawn_controls_screen),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(controls_menu)
+                    .with_system(render_controls_screen.after(controls_menu)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused).with_system(despawn_controls_screen),
+            );
+    }
+}
+
+fn config_path(file: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("dicethulhu").join(file))
+}
+
+fn load_config<T: DeserializeOwned>(file: &str) -> Option<T> {
+    config_path(file)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| match ron::from_str(&contents) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("Ignoring invalid {file}: {e}");
+                None
+            }
+        })
+}
+
+fn save_config<T: Serialize>(file: &str, value: &T) {
+    let path = match config_path(file) {
+        Some(path) => path,
+        None => return,
+    };
+
+    let result = ron::ser::to_string_pretty(value, Default::default())
+        .map_err(|e| e.to_string())
+        .and_then(|contents| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            fs::write(&path, contents).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        warn!("Failed to save {}: {e}", path.display());
+    }
+}
+
+fn load_bindings(mut commands: Commands) {
+    let bindings = load_config(BINDINGS_FILE).unwrap_or_else(default_bindings);
+    commands.insert_resource(Bindings(bindings));
+}
+
+fn save_bindings(bindings: &Bindings) {
+    save_config(BINDINGS_FILE, &bindings.0);
+}
+
+fn load_accessibility(mut commands: Commands) {
+    let accessibility: Accessibility = load_config(ACCESSIBILITY_FILE).unwrap_or_default();
+    commands.insert_resource(accessibility);
+}
+
+fn apply_bindings(bindings: Res<Bindings>, mut query: Query<&mut InputMap>) {
+    if !bindings.is_changed() {
+        return;
+    }
+
+    for mut map in query.iter_mut() {
+        map.bindings = bindings.0.clone();
+    }
+}
+
+fn toggle_pause(
+    query: Query<&ActionState>,
+    menu: Res<ControlsMenu>,
+    mut state: ResMut<State<GameState>>,
+) {
+    // The pause binding might be the button being bound
+    if menu.awaiting_input
+        || !query
+            .iter()
+            .any(|actions| actions.just_pressed(Action::Pause))
+    {
+        return;
+    }
+
+    match state.current() {
+        GameState::Play => {
+            let _ = state.push(GameState::Paused);
+        }
+        GameState::Paused => {
+            let _ = state.pop();
+        }
+        _ => {}
+    }
+}
+
+fn controls_menu(
+    mut menu: ResMut<ControlsMenu>,
+    mut bindings: ResMut<Bindings>,
+    mut accessibility: ResMut<Accessibility>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    pad_buttons: Res<Input<GamepadButton>>,
+) {
+    let pad_pressed = |button_type: GamepadButtonType| {
+        pad_buttons
+            .get_just_pressed()
+            .any(|button| button.button_type == button_type)
+    };
+
+    if menu.awaiting_input {
+        if keyboard.just_pressed(KeyCode::Back) {
+            menu.awaiting_input = false;
+            return;
+        }
+
+        let pressed = keyboard
+            .get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                mouse
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Mouse(*button))
+            })
+            .or_else(|| {
+                pad_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Pad(button.button_type))
+            });
+
+        if let Some(binding) = pressed {
+            let action = Action::ALL[menu.selected];
+            menu.message = match bindings.conflict(action, binding) {
+                Some(other) => format!("{binding} is already bound to {}", other.name()),
+                None => {
+                    bindings.rebind(action, binding);
+                    save_bindings(&bindings);
+                    String::new()
+                }
+            };
+            menu.awaiting_input = false;
+        }
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Up) || pad_pressed(GamepadButtonType::DPadUp) {
+        menu.selected = (menu.selected + MENU_ROWS - 1) % MENU_ROWS;
+        menu.message.clear();
+    }
+    if keyboard.just_pressed(KeyCode::Down) || pad_pressed(GamepadButtonType::DPadDown) {
+        menu.selected = (menu.selected + 1) % MENU_ROWS;
+        menu.message.clear();
+    }
+    if keyboard.just_pressed(KeyCode::Return) || pad_pressed(GamepadButtonType::South) {
+        match menu.selected_row() {
+            MenuRow::Binding(_) => menu.awaiting_input = true,
+            MenuRow::Toggle(toggle) => {
+                toggle.flip(&mut accessibility);
+                save_config(ACCESSIBILITY_FILE, &*accessibility);
+            }
+        }
+        menu.message.clear();
+    }
+    if keyboard.just_pressed(KeyCode::R) || pad_pressed(GamepadButtonType::North) {
+        bindings.0 = default_bindings();
+        save_bindings(&bindings);
+        menu.message = "Controls reset to defaults".to_string();
+    }
+}
+
+fn spawn_controls_screen(
+    mut commands: Commands,
+    mut menu: ResMut<ControlsMenu>,
+    font: Res<UiFont>,
+) {
+    *menu = ControlsMenu::default();
+
+    let style = TextStyle {
+        font: font.0.clone(),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+    let hint_style = TextStyle {
+        color: Color::GRAY,
+        ..style.clone()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexStart,
+                padding: UiRect::all(Val::Px(80.0)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.75).into(),
+            ..Default::default()
+        })
+        .insert(ControlsScreen)
+        .insert(Name::from("Controls Screen"))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section("PAUSED - SETTINGS", style.clone()));
+
+            for action in Action::ALL {
+                parent
+                    .spawn_bundle(TextBundle::from_section("", style.clone()))
+                    .insert(MenuRow::Binding(action));
+            }
+            for toggle in Toggle::ALL {
+                parent
+                    .spawn_bundle(TextBundle::from_section("", style.clone()))
+                    .insert(MenuRow::Toggle(toggle));
+            }
+
+            parent
+                .spawn_bundle(TextBundle::from_section("", style.clone()))
+                .insert(MenuMessage);
+            parent.spawn_bundle(TextBundle::from_section(
+                "Up/Down: select   Enter: rebind/toggle   Backspace: cancel   R: reset controls",
+                hint_style,
+            ));
+        });
+}
+
+fn render_controls_screen(
+    menu: Res<ControlsMenu>,
+    bindings: Res<Bindings>,
+    accessibility: Res<Accessibility>,
+    mut rows: Query<(&MenuRow, &mut Text), Without<MenuMessage>>,
+    mut message: Query<&mut Text, With<MenuMessage>>,
+    added: Query<(), Added<MenuRow>>,
+) {
+    if !menu.is_changed()
+        && !bindings.is_changed()
+        && !accessibility.is_changed()
+        && added.is_empty()
+    {
+        return;
+    }
+
+    for (row, mut text) in rows.iter_mut() {
+        let selected = menu.selected_row() == *row;
+        let (name, value) = match row {
+            MenuRow::Binding(action) => (
+                action.name(),
+                match selected && menu.awaiting_input {
+                    true => "press a button...".to_string(),
+                    false => bindings
+                        .0
+                        .iter()
+                        .filter(|(bound_action, _)| bound_action == action)
+                        .map(|(_, binding)| binding.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                },
+            ),
+            MenuRow::Toggle(toggle) => (
+                toggle.name(),
+                match toggle.enabled(&accessibility) {
+                    true => "On".to_string(),
+                    false => "Off".to_string(),
+                },
+            ),
+        };
+
+        let section = &mut text.sections[0];
+        section.value = format!("{} {name:<12}{value}", if selected { ">" } else { " " });
+        section.style.color = match selected {
+            true => Color::YELLOW,
+            false => Color::WHITE,
+        };
+    }
+
+    if let Ok(mut text) = message.get_single_mut() {
+        text.sections[0].value = menu.message.clone();
+    }
+}
+
+fn despawn_controls_screen(mut commands: Commands, query: Query<Entity, With<ControlsScreen>>) {
+    for id in query.iter() {
+        commands.entity(id).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_used_by_another_action_conflicts() {
+        let bindings = Bindings(default_bindings());
+
+        assert_eq!(
+            bindings.conflict(Action::Fire, Binding::Key(KeyCode::W)),
+            Some(Action::Jump)
+        );
+        assert_eq!(
+            bindings.conflict(Action::Jump, Binding::Key(KeyCode::W)),
+            None
+        );
+        assert_eq!(
+            bindings.conflict(Action::Jump, Binding::Key(KeyCode::J)),
+            None
+        );
+    }
+
+    #[test]
+    fn rebinding_replaces_only_the_same_device() {
+        let mut bindings = Bindings(default_bindings());
+        bindings.rebind(Action::Fire, Binding::Key(KeyCode::J));
+
+        let fire: Vec<Binding> = bindings
+            .0
+            .iter()
+            .filter(|(action, _)| *action == Action::Fire)
+            .map(|(_, binding)| *binding)
+            .collect();
+        assert_eq!(
+            fire,
+            vec![
+                Binding::Pad(GamepadButtonType::West),
+                Binding::Pad(GamepadButtonType::RightTrigger2),
+                Binding::Key(KeyCode::J),
+            ]
+        );
+        assert_eq!(
+            bindings.conflict(Action::Jump, Binding::Key(KeyCode::J)),
+            Some(Action::Fire)
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use std::time::Duration;
+
+use crate::{
+    health::{DamageEvent, DamageKind, DamageScale, DamageSystem, Hitstun, Invuln},
+    GameState,
+};
+
+pub struct StatusPlugin;
+
+/// Seconds between burn damage ticks. Each stack deals one point per tick.
+const BURN_TICK_SECS: f32 = 0.5;
+/// Speed lost per stack of slow.
+const SLOW_PER_STACK: f32 = 0.25;
+/// Frozen targets shatter, taking extra damage from every hit.
+const FREEZE_DAMAGE_SCALE: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Inspectable)]
+pub enum StatusKind {
+    #[default]
+    Burn,
+    Slow,
+    Freeze,
+    Stun,
+}
+
+/// How applying an effect that is already running combines with it.
+enum Stacking {
+    /// Keeps one stack and resets the duration if the new one is longer.
+    Refresh,
+    /// Adds a stack, up to the limit, and refreshes the duration.
+    Intensify(u8),
+}
+
+impl StatusKind {
+    fn stacking(&self) -> Stacking {
+        match self {
+            StatusKind::Burn | StatusKind::Slow => Stacking::Intensify(3),
+            StatusKind::Freeze | StatusKind::Stun => Stacking::Refresh,
+        }
+    }
+
+    fn tint(&self) -> Color {
+        match self {
+            StatusKind::Burn => Color::rgb(1.0, 0.55, 0.35),
+            StatusKind::Slow => Color::rgb(0.7, 0.6, 1.0),
+            StatusKind::Freeze => Color::rgb(0.5, 0.8, 1.0),
+            StatusKind::Stun => Color::rgb(1.0, 1.0, 0.5),
+        }
+    }
+}
+
+/// A status for a weapon or attack to inflict, kept as plain data so it can live in consts.
+#[derive(Clone, Copy, Debug)]
+pub struct StatusApplication {
+    pub kind: StatusKind,
+    pub secs: f32,
+}
+
+/// Inflicts `status` on `target` if it has `StatusEffects`.
+pub struct ApplyStatus {
+    pub target: Entity,
+    pub status: StatusApplication,
+}
+
+#[derive(Clone, Debug, Default, Inspectable)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub remaining: f32,
+    pub stacks: u8,
+    since_tick: f32,
+}
+
+#[derive(Component, Debug, Default, Inspectable)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+    /// Sprite colour from before any effect tinted it.
+    #[inspectable(ignore)]
+    base_color: Option<Color>,
+}
+
+impl StatusEffects {
+    fn get(&self, kind: StatusKind) -> Option<&StatusEffect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn apply(&mut self, status: StatusApplication) {
+        let existing = self
+            .effects
+            .iter_mut()
+            .find(|effect| effect.kind == status.kind);
+
+        match existing {
+            Some(effect) => {
+                if let Stacking::Intensify(max) = status.kind.stacking() {
+                    effect.stacks = (effect.stacks + 1).min(max);
+                }
+                effect.remaining = effect.remaining.max(status.secs);
+            }
+            None => self.effects.push(StatusEffect {
+                kind: status.kind,
+                remaining: status.secs,
+                stacks: 1,
+                since_tick: 0.0,
+            }),
+        }
+    }
+
+    /// Multiplies movement speed. Freezing stops it outright.
+    pub fn speed_multiplier(&self) -> f32 {
+        match self.get(StatusKind::Freeze) {
+            Some(_) => 0.0,
+            None => self.get(StatusKind::Slow).map_or(1.0, |slow| {
+                (1.0 - SLOW_PER_STACK * slow.stacks as f32).max(0.0)
+            }),
+        }
+    }
+
+    /// How fast cooldowns such as attack timers count down.
+    pub fn cooldown_rate(&self) -> f32 {
+        self.speed_multiplier()
+    }
+
+    /// Only the most severe effect shows, since blended tints turn to mud.
+    fn tint(&self) -> Option<Color> {
+        [
+            StatusKind::Freeze,
+            StatusKind::Stun,
+            StatusKind::Burn,
+            StatusKind::Slow,
+        ]
+        .into_iter()
+        .find(|kind| self.get(*kind).is_some())
+        .map(|kind| kind.tint())
+    }
+}
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatus>()
+            .add_system(apply_status.before(tick_status))
+            // Burn ticks must land before a death despawns the entity under these commands
+            .add_system_set(
+                SystemSet::on_update(GameState::Play).with_system(tick_status.before(DamageSystem)),
+            )
+            .add_system(tint_status.after(tick_status));
+    }
+}
+
+fn apply_status(mut events: EventReader<ApplyStatus>, mut query: Query<&mut StatusEffects>) {
+    for event in events.iter() {
+        if let Ok(mut effects) = query.get_mut(event.target) {
+            effects.apply(event.status);
+        }
+    }
+}
+
+fn tick_status(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut StatusEffects,
+        Option<&Hitstun>,
+        Option<&DamageScale>,
+    )>,
+    mut damage: EventWriter<DamageEvent>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (id, mut effects, hitstun, scale) in query.iter_mut() {
+        // Leave change detection alone once nothing is running
+        if effects.effects.is_empty() {
+            continue;
+        }
+
+        for effect in effects.effects.iter_mut() {
+            effect.remaining -= dt;
+            effect.since_tick += dt;
+
+            if effect.kind == StatusKind::Burn && effect.since_tick >= BURN_TICK_SECS {
+                effect.since_tick -= BURN_TICK_SECS;
+                damage.send(DamageEvent {
+                    target: id,
+                    amount: effect.stacks,
+                    source: None,
+                    kind: DamageKind::Burn,
+                    knockback: Vec2::ZERO,
+                    status: None,
+                });
+            }
+        }
+        effects.effects.retain(|effect| effect.remaining > 0.0);
+
+        // Stun reuses hitstun, which already stops input and AI
+        if let (Some(stun), None) = (effects.get(StatusKind::Stun), hitstun) {
+            commands
+                .entity(id)
+                .insert(Hitstun::new(Duration::from_secs_f32(stun.remaining)));
+        }
+        match (effects.get(StatusKind::Freeze), scale) {
+            (Some(_), None) => {
+                commands.entity(id).insert(DamageScale(FREEZE_DAMAGE_SCALE));
+            }
+            (None, Some(_)) => {
+                commands.entity(id).remove::<DamageScale>();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Tints sprites by their most severe effect, underneath any hit flash.
+fn tint_status(
+    mut query: Query<
+        (
+            &mut StatusEffects,
+            &mut TextureAtlasSprite,
+            Option<&mut Invuln>,
+        ),
+        Changed<StatusEffects>,
+    >,
+) {
+    for (mut effects, mut sprite, invuln) in query.iter_mut() {
+        let tint = effects.tint();
+        let base = match (tint, effects.base_color) {
+            (_, Some(base)) => base,
+            (Some(_), None) => {
+                let base = invuln
+                    .as_ref()
+                    .and_then(|invuln| invuln.original())
+                    .unwrap_or(sprite.color);
+                effects.base_color = Some(base);
+                base
+            }
+            (None, None) => continue,
+        };
+
+        let color = match tint {
+            Some(tint) => Color::rgba(
+                base.r() * tint.r(),
+                base.g() * tint.g(),
+                base.b() * tint.b(),
+                base.a(),
+            ),
+            None => {
+                effects.base_color = None;
+                base
+            }
+        };
+
+        // Hit flashes put back their own copy of the colour when they end
+        match invuln {
+            Some(mut invuln) => {
+                invuln.set_original(color);
+                if !invuln.flashing() {
+                    sprite.color = color;
+                }
+            }
+            None => sprite.color = color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(kind: StatusKind, secs: f32) -> StatusApplication {
+        StatusApplication { kind, secs }
+    }
+
+    #[test]
+    fn intensifying_effects_stack_up_to_their_limit() {
+        let mut effects = StatusEffects::default();
+        for _ in 0..5 {
+            effects.apply(status(StatusKind::Burn, 2.0));
+        }
+
+        assert_eq!(effects.effects.len(), 1);
+        assert_eq!(effects.get(StatusKind::Burn).unwrap().stacks, 3);
+    }
+
+    #[test]
+    fn refreshing_effects_keep_one_stack_and_the_longer_duration() {
+        let mut effects = StatusEffects::default();
+        effects.apply(status(StatusKind::Freeze, 2.0));
+        effects.apply(status(StatusKind::Freeze, 1.0));
+
+        let freeze = effects.get(StatusKind::Freeze).unwrap();
+        assert_eq!(freeze.stacks, 1);
+        assert_eq!(freeze.remaining, 2.0);
+
+        effects.apply(status(StatusKind::Freeze, 3.0));
+        assert_eq!(effects.get(StatusKind::Freeze).unwrap().remaining, 3.0);
+    }
+
+    #[test]
+    fn slow_stacks_reduce_speed_and_freeze_stops_it() {
+        let mut effects = StatusEffects::default();
+        effects.apply(status(StatusKind::Slow, 1.0));
+        effects.apply(status(StatusKind::Slow, 1.0));
+        assert_eq!(effects.speed_multiplier(), 1.0 - SLOW_PER_STACK * 2.0);
+
+        effects.apply(status(StatusKind::Freeze, 1.0));
+        assert_eq!(effects.speed_multiplier(), 0.0);
+    }
+}
@@ -1,57 +1,344 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, sprite::Anchor, transform::TransformSystem};
+use std::f32::consts::PI;
 
-use crate::{health::Health, player::Player, GameState};
+use crate::{
+    health::Health,
+    player::{Dash, Gun, Player},
+    GameState, GunSheet,
+};
 
 pub struct UiPlugin;
 
 const HEART_WIDTH: f32 = 18.0;
+/// Frames of Hearts.png, with the filled-in white heart standing for a half.
+pub const HEART_FULL: usize = 0;
+pub const HEART_HALF: usize = 1;
+pub const HEART_EMPTY: usize = 2;
+const HEART_PADDING: f32 = 5.0;
+/// Heart containers that fit in a row without meeting the other player's across the view.
+pub const MAX_HEARTS_PER_ROW: u8 = 6;
+/// How far the first heart sits in from its corner of the view.
+const HUD_MARGIN: Vec2 = Vec2::new(14.0, 14.0);
+const HEART_BOUNCE_SECS: f32 = 0.3;
+const HEART_BOUNCE_SCALE: f32 = 0.4;
+const DASH_METER_SIZE: Vec2 = Vec2::new(23.0, 2.0);
 
-struct HeartsSheet(Handle<TextureAtlas>);
+pub struct HeartsSheet(pub Handle<TextureAtlas>);
 struct AsciiSheet(Handle<TextureAtlas>);
+pub struct UiFont(pub Handle<Font>);
 
+/// One heart container in a player's row, counted from the corner.
 #[derive(Component)]
-pub struct UpdatedHealth;
+struct UiHeart {
+    player: usize,
+    slot: u8,
+}
+/// Grows a heart that was just gained and shrinks one that was just lost.
+#[derive(Component)]
+struct HeartBounce {
+    timer: Timer,
+    gained: bool,
+}
+/// Keeps a HUD sprite a fixed offset from a corner of the view, whatever the window or zoom.
+#[derive(Component)]
+struct HudAnchor {
+    /// -1 to 1 on each axis, so `(1, 1)` is the top right.
+    corner: Vec2,
+    offset: Vec2,
+}
 #[derive(Component)]
-struct UiHeart;
+struct UiWeapon(usize);
+#[derive(Component)]
+struct UiWeaponName(usize);
+#[derive(Component)]
+struct UiDashMeter(usize);
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system_to_stage(StartupStage::PreStartup, load_graphics)
+            .add_system(spawn_player_hud)
+            .add_system(clear_dead_hearts)
+            // Place freshly spawned HUD sprites before they are first drawn
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                anchor_hud.before(TransformSystem::TransformPropagate),
+            )
+            .add_system(bounce_hearts)
             .add_system_set(
-                SystemSet::on_update(GameState::Play).with_system(render_player_health),
+                SystemSet::on_update(GameState::Play)
+                    .with_system(render_player_health)
+                    .with_system(render_weapon_indicator)
+                    .with_system(render_dash_meter),
             );
     }
 }
 
+/// Which corner a player's HUD sits in and which way along x it grows. The first player's is in
+/// the top right and the second's mirrors it in the top left.
+fn hud_corner(player: usize) -> (Vec2, f32) {
+    match player {
+        0 => (Vec2::new(1.0, 1.0), -1.0),
+        _ => (Vec2::new(-1.0, 1.0), 1.0),
+    }
+}
+
+/// One container per two points of max health, filled from the corner.
+fn containers(health: &Health) -> u8 {
+    health.max.div_ceil(2)
+}
+
+fn heart_index(health: &Health, slot: u8) -> usize {
+    match health.health.saturating_sub(slot * 2) {
+        0 => HEART_EMPTY,
+        1 => HEART_HALF,
+        _ => HEART_FULL,
+    }
+}
+
+/// Updates only the hearts that changed, adding or removing containers as max health moves.
 fn render_player_health(
     mut commands: Commands,
     heart_sheet: Res<HeartsSheet>,
-    query: Query<(Entity, &Health), (With<Player>, Added<UpdatedHealth>)>,
-    hearts: Query<Entity, With<UiHeart>>,
+    query: Query<(&Player, &Health), Changed<Health>>,
+    mut hearts: Query<(Entity, &UiHeart, &mut TextureAtlasSprite)>,
 ) {
-    const PADDING: f32 = 5.0;
+    for (player, health) in query.iter() {
+        let (corner, direction) = hud_corner(player.id);
+        let containers = containers(health);
+        let mut shown = vec![false; containers as usize];
 
-    let pos = Vec3::new(146.0, 76.0, 200.0);
-    if let Ok((id, health)) = query.get_single() {
-        for heart in hearts.iter() {
-            commands.entity(heart).despawn_recursive();
+        for (heart, UiHeart { slot, .. }, mut sprite) in hearts
+            .iter_mut()
+            .filter(|(_, heart, _)| heart.player == player.id)
+        {
+            if *slot >= containers {
+                commands.entity(heart).despawn_recursive();
+                continue;
+            }
+            shown[*slot as usize] = true;
+
+            let index = heart_index(health, *slot);
+            if sprite.index != index {
+                commands.entity(heart).insert(HeartBounce {
+                    timer: Timer::from_seconds(HEART_BOUNCE_SECS, false),
+                    // Frames go from full to empty
+                    gained: index < sprite.index,
+                });
+                sprite.index = index;
+            }
         }
 
-        for i in 0..health.health {
+        for slot in (0..containers).filter(|slot| !shown[*slot as usize]) {
+            let offset = Vec2::new(
+                direction * (HUD_MARGIN.x + (HEART_WIDTH + HEART_PADDING) * slot as f32),
+                -HUD_MARGIN.y,
+            );
             commands
                 .spawn_bundle(SpriteSheetBundle {
-                    sprite: TextureAtlasSprite::new(0),
+                    sprite: TextureAtlasSprite::new(heart_index(health, slot)),
                     texture_atlas: heart_sheet.0.clone(),
-                    transform: Transform {
-                        translation: pos - Vec3::new((HEART_WIDTH + PADDING) * i as f32, 0.0, 0.0),
-                        ..Default::default()
-                    },
+                    transform: Transform::from_xyz(0.0, 0.0, 200.0),
                     ..Default::default()
                 })
-                .insert(UiHeart)
+                .insert(HudAnchor { corner, offset })
+                .insert(UiHeart {
+                    player: player.id,
+                    slot,
+                })
                 .insert(Name::from("Heart"));
+        }
+    }
+}
+
+fn bounce_hearts(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &mut HeartBounce)>,
+    time: Res<Time>,
+) {
+    for (id, mut transform, mut bounce) in query.iter_mut() {
+        bounce.timer.tick(time.delta());
+
+        let amount = HEART_BOUNCE_SCALE * (bounce.timer.percent() * PI).sin();
+        transform.scale = Vec3::splat(match bounce.gained {
+            true => 1.0 + amount,
+            false => 1.0 - amount,
+        });
 
-            commands.entity(id).remove::<UpdatedHealth>();
+        if bounce.timer.finished() {
+            transform.scale = Vec3::ONE;
+            commands.entity(id).remove::<HeartBounce>();
+        }
+    }
+}
+
+type HudCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static OrthographicProjection, &'static Transform),
+    (With<Camera2d>, Without<HudAnchor>),
+>;
+
+/// Places anchors in the camera's frame, so they ride along with shake and any camera movement.
+fn anchor_hud(camera_query: HudCameraQuery, mut query: Query<(&HudAnchor, &mut Transform)>) {
+    let (projection, camera) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let half_size = Vec2::new(
+        projection.right - projection.left,
+        projection.top - projection.bottom,
+    ) * projection.scale
+        / 2.0;
+
+    for (anchor, mut transform) in query.iter_mut() {
+        let position = camera.mul_vec3((anchor.corner * half_size + anchor.offset).extend(0.0));
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        transform.rotation = camera.rotation;
+    }
+}
+
+/// Dead players are despawned before their hearts are redrawn, so clear their row here.
+fn clear_dead_hearts(
+    mut commands: Commands,
+    hearts: Query<(Entity, &UiHeart)>,
+    players: Query<&Player>,
+) {
+    for (heart, UiHeart { player: owner, .. }) in hearts.iter() {
+        if players.iter().all(|player| player.id != *owner) {
+            commands.entity(heart).despawn_recursive();
+        }
+    }
+}
+
+fn spawn_player_hud(
+    mut commands: Commands,
+    gun_sheet: Res<GunSheet>,
+    font: Res<UiFont>,
+    players: Query<&Player, Added<Player>>,
+    existing: Query<&UiWeapon>,
+) {
+    for player in players.iter() {
+        // Revived players reuse the HUD from before they died
+        if existing.iter().any(|UiWeapon(owner)| *owner == player.id) {
+            continue;
+        }
+        let (corner, direction) = hud_corner(player.id);
+
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite::new(0),
+                texture_atlas: gun_sheet.0.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, 200.0),
+                ..Default::default()
+            })
+            .insert(HudAnchor {
+                corner,
+                offset: Vec2::new(direction * (HUD_MARGIN.x + 2.0), -HUD_MARGIN.y - 14.0),
+            })
+            .insert(UiWeapon(player.id))
+            .insert(Name::from("Weapon Indicator"));
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(DASH_METER_SIZE),
+                    anchor: match direction < 0.0 {
+                        true => Anchor::CenterRight,
+                        false => Anchor::CenterLeft,
+                    },
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, 200.0),
+                ..Default::default()
+            })
+            .insert(HudAnchor {
+                corner,
+                offset: Vec2::new(direction * (HUD_MARGIN.x - 9.5), -HUD_MARGIN.y - 21.0),
+            })
+            .insert(UiDashMeter(player.id))
+            .insert(Name::from("Dash Meter"));
+
+        let position = match direction < 0.0 {
+            true => UiRect {
+                top: Val::Px(170.0),
+                right: Val::Px(40.0),
+                ..Default::default()
+            },
+            false => UiRect {
+                top: Val::Px(170.0),
+                left: Val::Px(40.0),
+                ..Default::default()
+            },
+        };
+        commands
+            .spawn_bundle(
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    position,
+                    ..Default::default()
+                }),
+            )
+            .insert(UiWeaponName(player.id))
+            .insert(Name::from("Weapon Name"));
+    }
+}
+
+fn render_weapon_indicator(
+    gun_query: Query<(&Parent, &Gun)>,
+    players: Query<&Player>,
+    mut icon_query: Query<(&UiWeapon, &mut TextureAtlasSprite)>,
+    mut name_query: Query<(&UiWeaponName, &mut Text)>,
+) {
+    for (parent, gun) in gun_query.iter() {
+        let player = match players.get(parent.get()) {
+            Ok(player) => player.id,
+            Err(_) => continue,
+        };
+        let weapon = gun.weapon;
+
+        for (_, mut icon) in icon_query
+            .iter_mut()
+            .filter(|(UiWeapon(owner), _)| *owner == player)
+        {
+            icon.index = weapon.sprite.idle;
+            icon.color = weapon.sprite.color;
+        }
+        for (_, mut text) in name_query
+            .iter_mut()
+            .filter(|(UiWeaponName(owner), _)| *owner == player)
+        {
+            if text.sections[0].value != weapon.name {
+                text.sections[0].value = weapon.name.to_string();
+            }
+        }
+    }
+}
+
+fn render_dash_meter(
+    dash_query: Query<(&Player, &Dash)>,
+    mut meter_query: Query<(&UiDashMeter, &mut Sprite)>,
+) {
+    for (player, dash) in dash_query.iter() {
+        let charge = dash.cooldown.percent();
+
+        for (_, mut sprite) in meter_query
+            .iter_mut()
+            .filter(|(UiDashMeter(owner), _)| *owner == player.id)
+        {
+            sprite.custom_size = Some(DASH_METER_SIZE * Vec2::new(charge, 1.0));
+            sprite.color = match charge >= 1.0 {
+                true => Color::rgb(0.6, 0.8, 1.0),
+                false => Color::GRAY,
+            };
         }
     }
 }
@@ -72,4 +359,35 @@ fn load_graphics(
     );
     let atlas_handle = texture_atlases.add(atlas);
     commands.insert_resource(HeartsSheet(atlas_handle));
+
+    let font = assets.load("fonts/DejaVuSansMono.ttf");
+    commands.insert_resource(UiFont(font));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PIXEL_WIDTH;
+
+    #[test]
+    fn hearts_fill_half_points_from_the_corner() {
+        let health = Health { health: 3, max: 6 };
+
+        assert_eq!(heart_index(&health, 0), HEART_FULL);
+        assert_eq!(heart_index(&health, 1), HEART_HALF);
+        assert_eq!(heart_index(&health, 2), HEART_EMPTY);
+    }
+
+    #[test]
+    fn odd_max_health_rounds_up_to_a_container() {
+        assert_eq!(containers(&Health::new(10)), 5);
+        assert_eq!(containers(&Health::new(11)), 6);
+        assert_eq!(containers(&Health::new(0)), 0);
+    }
+
+    #[test]
+    fn full_rows_leave_a_gap_between_players() {
+        let last = HUD_MARGIN.x + (HEART_WIDTH + HEART_PADDING) * (MAX_HEARTS_PER_ROW - 1) as f32;
+        assert!(last + HEART_WIDTH / 2.0 < PIXEL_WIDTH / 2.0);
+    }
 }
@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::time::Duration;
+
+use crate::{
+    physics::{Collision, CollisionApp},
+    player::{Gun, Player},
+    status::{StatusApplication, StatusKind},
+    GameState, GunSheet,
+};
+
+pub struct WeaponPlugin;
+
+const PICKUP_HEIGHT: f32 = -64.0;
+
+pub struct Weapon {
+    pub name: &'static str,
+    pub cooldown: Duration,
+    /// Lasers fired per shot.
+    pub projectiles: u32,
+    /// Total angle in radians the lasers fan across. A single laser is jittered within it.
+    pub spread: f32,
+    pub speed: f32,
+    pub damage: u8,
+    /// How many enemies a laser passes through before it is used up.
+    pub piercing: u8,
+    /// Speed a hit adds to an enemy along the laser's path, in pixels per second.
+    pub knockback: f32,
+    /// Inflicted on every enemy a laser hits.
+    pub status: Option<StatusApplication>,
+    pub sprite: WeaponSprite,
+}
+
+/// `Gun.png` frames and the tint the weapon and its lasers are drawn with.
+pub struct WeaponSprite {
+    pub idle: usize,
+    pub firing: usize,
+    pub color: Color,
+}
+
+pub const WEAPONS: [Weapon; 4] = [
+    Weapon {
+        name: "Blaster",
+        cooldown: Duration::from_millis(100),
+        projectiles: 1,
+        spread: 0.0,
+        speed: 300.0,
+        damage: 1,
+        piercing: 0,
+        knockback: 0.0,
+        status: None,
+        sprite: WeaponSprite {
+            idle: 0,
+            firing: 1,
+            color: Color::WHITE,
+        },
+    },
+    Weapon {
+        name: "Scatter",
+        cooldown: Duration::from_millis(450),
+        projectiles: 5,
+        spread: 0.6,
+        speed: 250.0,
+        damage: 1,
+        piercing: 0,
+        knockback: 40.0,
+        status: Some(StatusApplication {
+            kind: StatusKind::Slow,
+            secs: 1.5,
+        }),
+        sprite: WeaponSprite {
+            idle: 0,
+            firing: 1,
+            color: Color::rgb(1.0, 0.6, 0.3),
+        },
+    },
+    Weapon {
+        name: "Repeater",
+        cooldown: Duration::from_millis(50),
+        projectiles: 1,
+        spread: 0.25,
+        speed: 350.0,
+        damage: 1,
+        piercing: 0,
+        knockback: 0.0,
+        status: Some(StatusApplication {
+            kind: StatusKind::Burn,
+            secs: 2.0,
+        }),
+        sprite: WeaponSprite {
+            idle: 0,
+            firing: 1,
+            color: Color::rgb(0.5, 1.0, 0.5),
+        },
+    },
+    Weapon {
+        name: "Railgun",
+        cooldown: Duration::from_millis(700),
+        projectiles: 1,
+        spread: 0.0,
+        speed: 600.0,
+        damage: 3,
+        piercing: 3,
+        knockback: 100.0,
+        status: Some(StatusApplication {
+            kind: StatusKind::Freeze,
+            secs: 1.0,
+        }),
+        sprite: WeaponSprite {
+            idle: 0,
+            firing: 1,
+            color: Color::rgb(0.5, 0.7, 1.0),
+        },
+    },
+];
+
+impl Weapon {
+    /// Directions for each laser of one shot fired along `aim`.
+    pub fn shot_directions(&self, aim: Vec2) -> Vec<Vec2> {
+        let aim_angle = aim.y.atan2(aim.x);
+        let mut rng = thread_rng();
+
+        (0..self.projectiles)
+            .map(|i| {
+                let offset = match self.projectiles {
+                    1 if self.spread > 0.0 => rng.gen_range(-self.spread..=self.spread) / 2.0,
+                    1 => 0.0,
+                    n => self.spread * (i as f32 / (n - 1) as f32 - 0.5),
+                };
+                let angle = aim_angle + offset;
+                Vec2::new(angle.cos(), angle.sin())
+            })
+            .collect()
+    }
+}
+
+/// A weapon lying in the arena, equipped by touching it.
+#[derive(Component)]
+pub struct WeaponPickup(pub &'static Weapon);
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_collision_pair::<WeaponPickup, Player>()
+            .add_system_set(SystemSet::on_enter(GameState::DiceRoll).with_system(spawn_pickup))
+            .add_system_set(SystemSet::on_update(GameState::Play).with_system(collect_pickup));
+    }
+}
+
+/// Each round leaves a weapon that no player is holding.
+fn spawn_pickup(
+    mut commands: Commands,
+    gun_sheet: Res<GunSheet>,
+    gun_query: Query<&Gun>,
+    pickups: Query<Entity, With<WeaponPickup>>,
+) {
+    for id in pickups.iter() {
+        commands.entity(id).despawn_recursive();
+    }
+
+    let choices: Vec<&'static Weapon> = WEAPONS
+        .iter()
+        .filter(|weapon| gun_query.iter().all(|gun| gun.weapon.name != weapon.name))
+        .collect();
+
+    let mut rng = thread_rng();
+    let weapon = match choices.choose(&mut rng) {
+        Some(weapon) => *weapon,
+        None => return,
+    };
+
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: weapon.sprite.idle,
+                color: weapon.sprite.color,
+                ..Default::default()
+            },
+            texture_atlas: gun_sheet.0.clone(),
+            transform: Transform {
+                translation: Vec3::new(rng.gen_range(-120.0..=120.0), PICKUP_HEIGHT, 90.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Collider::cuboid(11.5, 4.5))
+        .insert(Sensor)
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(WeaponPickup(weapon))
+        .insert(Name::from(format!("{} Pickup", weapon.name)));
+}
+
+fn collect_pickup(
+    mut commands: Commands,
+    mut collisions: EventReader<Collision<WeaponPickup, Player>>,
+    pickups: Query<&WeaponPickup>,
+    players: Query<&Children, With<Player>>,
+    mut guns: Query<&mut Gun>,
+) {
+    for collision in collisions.iter().filter(|collision| collision.started) {
+        if let (Ok(WeaponPickup(weapon)), Ok(children)) =
+            (pickups.get(collision.a), players.get(collision.b))
+        {
+            for child in children.iter() {
+                if let Ok(mut gun) = guns.get_mut(*child) {
+                    gun.equip(weapon);
+                }
+            }
+            commands.entity(collision.a).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weapon(name: &str) -> &'static Weapon {
+        WEAPONS.iter().find(|weapon| weapon.name == name).unwrap()
+    }
+
+    fn angle(direction: Vec2) -> f32 {
+        direction.y.atan2(direction.x)
+    }
+
+    #[test]
+    fn single_laser_without_spread_follows_the_aim() {
+        let directions = weapon("Blaster").shot_directions(Vec2::new(0.0, -2.0));
+
+        assert_eq!(directions.len(), 1);
+        assert!(directions[0].abs_diff_eq(Vec2::NEG_Y, 1e-6));
+    }
+
+    #[test]
+    fn single_laser_is_jittered_within_the_spread() {
+        let repeater = weapon("Repeater");
+        for _ in 0..100 {
+            let directions = repeater.shot_directions(Vec2::X);
+
+            assert_eq!(directions.len(), 1);
+            assert!(angle(directions[0]).abs() <= repeater.spread / 2.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn spread_fans_lasers_evenly_around_the_aim() {
+        let scatter = weapon("Scatter");
+        let aim = Vec2::new(1.0, 1.0);
+        let directions = scatter.shot_directions(aim);
+
+        assert_eq!(directions.len(), scatter.projectiles as usize);
+        let offsets: Vec<f32> = directions
+            .iter()
+            .map(|direction| angle(*direction) - angle(aim))
+            .collect();
+        assert!((offsets[0] + scatter.spread / 2.0).abs() < 1e-5);
+        assert!(offsets[2].abs() < 1e-5);
+        assert!((offsets[4] - scatter.spread / 2.0).abs() < 1e-5);
+        for direction in directions {
+            assert!((direction.length() - 1.0).abs() < 1e-5);
+        }
+    }
+}